@@ -0,0 +1,57 @@
+#!/usr/bin/env python3
+"""Drives `antfarm gym` with random actions, a starting point for a trainer.
+
+    cargo run --release -- gym --port 5555
+    python3 scripts/gym_client.py --port 5555
+"""
+import argparse
+import json
+import random
+import socket
+
+
+class AntFarmEnv:
+    def __init__(self, host="127.0.0.1", port=5555):
+        self.sock = socket.create_connection((host, port))
+        self.file = self.sock.makefile("rw")
+        self.spec = self._call({"cmd": "spec"})
+
+    def _call(self, request):
+        self.file.write(json.dumps(request) + "\n")
+        self.file.flush()
+        response = json.loads(self.file.readline())
+        if "error" in response:
+            raise RuntimeError(response["error"])
+        return response
+
+    def reset(self, seed=None):
+        return self._call({"cmd": "reset", "seed": seed})["observation"]
+
+    def step(self, action):
+        r = self._call({"cmd": "step", "action": action})
+        return r["observation"], r["reward"], r["done"], r["info"]
+
+    def close(self):
+        self._call({"cmd": "close"})
+        self.sock.close()
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--port", type=int, default=5555)
+    parser.add_argument("--episodes", type=int, default=3)
+    args = parser.parse_args()
+
+    env = AntFarmEnv(port=args.port)
+    for episode in range(args.episodes):
+        env.reset(seed=episode)
+        total, done, info = 0.0, False, {}
+        while not done:
+            _, reward, done, info = env.step(random.randrange(env.spec["actions"]))
+            total += reward
+        print(f"episode {episode}: {info['steps']} steps, reward {total:.3f}")
+    env.close()
+
+
+if __name__ == "__main__":
+    main()
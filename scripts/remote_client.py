@@ -0,0 +1,64 @@
+#!/usr/bin/env python3
+"""Pokes a running colony started with --remote, then prints its events.
+
+    cargo run --release -- --remote 5556
+    python3 scripts/remote_client.py --port 5556
+"""
+import argparse
+import json
+import socket
+
+
+class Remote:
+    def __init__(self, host="127.0.0.1", port=5556):
+        self.sock = socket.create_connection((host, port))
+        self.file = self.sock.makefile("rw")
+        self.events = []
+
+    def call(self, cmd, **args):
+        self.file.write(json.dumps(dict(cmd=cmd, **args)) + "\n")
+        self.file.flush()
+        # Events can arrive before the response
+        while True:
+            message = json.loads(self.file.readline())
+            if "event" in message:
+                self.events.append(message)
+                continue
+            if "error" in message:
+                raise RuntimeError(message["error"])
+            return message
+
+    def next_event(self):
+        if self.events:
+            return self.events.pop(0)
+        line = self.file.readline()
+        return json.loads(line) if line else None
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--host", default="127.0.0.1")
+    parser.add_argument("--port", type=int, default=5556)
+    args = parser.parse_args()
+
+    remote = Remote(args.host, args.port)
+    remote.call("subscribe", events=["deaths", "food_discovered", "fog_cleared"])
+
+    remote.call("pause", paused=True)
+    print("ids", remote.call("spawn_ants", x=80, y=80, count=3)["ids"])
+    remote.call("drop_food", x=100, y=80)
+    print("wall", remote.call("toggle_wall", col=5, row=5)["wall"])
+    remote.call("step", ticks=10)
+    print("ants", len(remote.call("query", what="ants")["ants"]))
+    print("stats", remote.call("query", what="stats"))
+    remote.call("pause", paused=False)
+
+    while True:
+        event = remote.next_event()
+        if event is None:
+            break
+        print(event)
+
+
+if __name__ == "__main__":
+    main()
@@ -1,13 +1,73 @@
 use crate::arena::*;
-use crate::food::{Food, FoodCreateEvent};
+use crate::food::{Food, FoodCreateEvent, FoodKind};
 use crate::arena::Size;
 use bevy::prelude::*;
-use rand::{prelude::Distribution, distributions::WeightedIndex, thread_rng};
+use crate::clock::SimClock;
+use crate::steering::*;
+use crate::collision::{Collider, SpatialGrid, slide};
+use crate::behaviour::Caste;
+use crate::genome::Genome;
+use crate::rng::SimRng;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use crate::plugins::RequireExt;
 
 const ANT_COLOR: Color = Color::rgb(0.7, 0.7, 0.7);
-const ANT_SPEED: f32 = 50.;
+pub const ANT_SPEED: f32 = 50.;
 const QUEEN_SPEED: f32 = 20.;
+// Radians per second
+const ANT_TURN_RATE: f32 = 6.;
+const QUEEN_TURN_RATE: f32 = 2.;
+// Radius workers see food within
+pub const ANT_VISION: f32 = 5.0;
+
+// Spawns the starting colony
+#[derive(Clone)]
+pub struct ColonySpawner(pub Arc<dyn Fn(&mut Commands) + Send + Sync>);
+
+impl ColonySpawner {
+    // Workers piled up by the queen, how the farm has always started
+    pub fn workers(count: usize) -> Self {
+        ColonySpawner(Arc::new(move |commands: &mut Commands| {
+            for _ in 0..count {
+                commands.spawn_bundle(AntBundle::default());
+            }
+            commands.spawn_bundle(QueenBundle::default());
+        }))
+    }
+}
+
+impl Default for ColonySpawner {
+    fn default() -> Self {
+        ColonySpawner::workers(30)
+    }
+}
+
+// Colony wide tunables, for sweeps and experiments.  Decision thresholds
+// other than food_goal_hunger live in assets/behaviour.  Workers with a
+// Genome go by its hunger_rate and food_goal_hunger instead.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AntParams {
+    // Hunger pct lost per second
+    pub hunger_rate: f32,
+    // Multiplies how fast every kind of food is eaten
+    pub eat_rate: f32,
+    // Hunger below which workers go for food they know of, or take some from a nestmate
+    pub food_goal_hunger: f32,
+}
+
+impl Default for AntParams {
+    fn default() -> Self {
+        AntParams {
+            hunger_rate: HUNGER_DEGRADATION_RATE,
+            eat_rate: 1.0,
+            food_goal_hunger: FOOD_GOAL_HUNGER,
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct KnownFood {
@@ -15,18 +75,31 @@ pub struct KnownFood {
 }
 
 pub struct AntDeathEvent {
-    ent: Entity,
+    pub ent: Entity,
+}
+
+// Where and how much food an ant ate this tick
+pub struct FoodEatenEvent {
+    pub ant: Entity,
+    pub at: Position,
+    pub amount: f32,
+}
+
+// An ant saw food no one knew about
+pub struct FoodDiscoveredEvent {
+    pub food: Entity,
+    pub ant: Entity,
+    pub at: Position,
 }
 
 #[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
-enum AntPhase {
+pub enum AntPhase {
     CleanFood,
     FindFood,
-    FoodGoal,
 }
 
 #[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
-enum BigPhase {
+pub enum BigPhase {
     Decide,
     Move,
     Act,
@@ -39,7 +112,15 @@ impl Plugin for AntPlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_resource(KnownFood::default())
+            .init_resource::<SteeringWeights>()
+            .init_resource::<AntParams>()
+            .init_resource::<ColonySpawner>()
+            .requires::<SimClock>("AntPlugin", "ClockPlugin")
+            .requires::<SpatialGrid>("AntPlugin", "CollisionPlugin")
+            .requires::<Events<FoodCreateEvent>>("AntPlugin", "FoodPlugin")
             .add_event::<AntDeathEvent>()
+            .add_event::<FoodEatenEvent>()
+            .add_event::<FoodDiscoveredEvent>()
             .add_startup_system(spawn_ant)
             .add_system_set(
                 SystemSet::new()
@@ -51,10 +132,6 @@ impl Plugin for AntPlugin {
                         .label(AntPhase::FindFood)
                         .after(AntPhase::CleanFood)
                     )
-                    .with_system(add_food_goal
-                        .label(AntPhase::FoodGoal)
-                        .after(AntPhase::FindFood)
-                    )
             )
             .add_system_set(
                 SystemSet::new()
@@ -68,7 +145,6 @@ impl Plugin for AntPlugin {
                     .label(BigPhase::Act)
                     .after(BigPhase::Move)
                     .with_system(start_eat_food)
-                    .with_system(ant_begin_ai)
             )
             .add_system_set(
                 SystemSet::new()
@@ -82,207 +158,166 @@ impl Plugin for AntPlugin {
                     .label(BigPhase::Cleanup)
                     .after(BigPhase::Ambient)
                     .with_system(ant_coloration)
+                    .with_system(heading_rotation)
                     .with_system(ant_death_handler)
             );
     }
 }
 
+// Forget food that's gone or gone off
 fn clean_food(
     mut known_foods: ResMut<KnownFood>,
-    all_food: Query<Entity, With<Food>>,
+    all_food: Query<(Entity, &Food)>,
 ) {
     let mut af = std::collections::HashSet::<Entity>::new();
-    all_food.iter().for_each(|e| { af.insert(e); });
+    all_food.iter()
+        .filter(|(_, f)| !f.is_rotten())
+        .for_each(|(e, _)| { af.insert(e); });
     known_foods.locs = known_foods.locs.iter_mut()
         .filter(|e| af.contains(*e))
         .map(|e| *e)
         .collect();
 }
 
-fn ant_begin_ai(
-    time: Res<Time>,
-    mut commands: Commands,
-    unassigned_ants: Query<Entity, (With<Ant>, Without<AntEating>, Without<AntAI>)>,
-    mut ais: Query<&mut AntAI, With<Ant>>,
-) {
-    for ant in unassigned_ants.iter() {
-        commands.entity(ant).insert(AntAI::default());
-    }
-
-    let dt = time.delta_seconds();
-    for mut ai in ais.iter_mut() {
-
-        // Handle special AI first
-        match ai.ai {
-            // Need to set
-            AiGoal::None => {
-                *ai = AntAI::random_move_ai();
-            },
-            // Remain until explicitly cleared
-            AiGoal::Wait | AiGoal::Destination{..} => continue,
-            _ => (),
-        }
-
-        // Now do duration
-        if ai.duration > 0. {
-            ai.duration -= dt;
-        }
-        if ai.duration <= 0. {
-            *ai = AntAI::random_move_ai();
-        }
-    }
-}
-
 
 fn ant_movement(
-    time: Res<Time>,
-    mut q: QuerySet<(
-        QueryState<(&Position, &Size), With<Ant>>, // ant positions for filtering colliders
-        QueryState<(&Position, &Size), With<Collides>>, // possible colliders
-        QueryState<(&mut Position, &Size, &mut AntAI, Option<&Queen>), With<Ant>>, // ant positions for moving the ants
-    )>,
+    clock: Res<SimClock>,
+    weights: Res<SteeringWeights>,
+    grid: Res<SpatialGrid>,
+    mut ants: Query<(Entity, &mut Position, &Size, &CollisionGroups, &mut Heading, &mut Velocity, &AntAI, Option<&Queen>, Option<&Genome>), With<Ant>>,
 ) {
 
-    let dt = time.delta_seconds();
-    let d_r = dt * ANT_SPEED;
-
-    let ant_start: Vec<(Position, Size)> = q.q0().iter().map(|(p, s)| (*p, *s)).collect();
+    let dt = clock.delta_seconds();
+    if dt <= 0. {
+        return;
+    }
 
-    // Trim occupied down to only things within 2 move spaces.
-    let occupied: Vec<(Position, Size)> = q.q1().iter()
-        .filter(|(p_obj, s_obj)| {
-            ant_start.iter()
-                .any(|(p_ant, s_ant)| {
-                    dist_between(p_ant, s_ant, p_obj, s_obj) < d_r * 2.
-                })
-        })
-        .map(|(p, s)| (*p, *s))
-        .collect();
+    let max_width = ARENA_TILE_SIDE * ARENA_WIDTH_TILES as f32;
+    let max_height = ARENA_TILE_SIDE * ARENA_HEIGHT_TILES as f32;
 
-    for (mut pos, size, ai, opt_queen) in q.q2().iter_mut() {
+    let mut rng = SimRng;
+    for (e, mut pos, size, groups, mut heading, mut velocity, ai, opt_queen, opt_genome) in ants.iter_mut() {
 
-        let d_r = if opt_queen.is_some() {
-            dt * QUEEN_SPEED
-        } else {
-            d_r
+        // Workers move by their own genes, the queen at her own pace
+        let (mut speed, turn_rate, weights) = match (opt_queen, opt_genome) {
+            (Some(_), _) => (QUEEN_SPEED, QUEEN_TURN_RATE, *weights),
+            (None, Some(genome)) => (genome.speed, ANT_TURN_RATE, genome.steering()),
+            (None, None) => (ANT_SPEED, ANT_TURN_RATE, *weights),
         };
 
+        let here = Vec2::from((pos.x, pos.y));
+        let mut steer = Vec2::ZERO;
         match ai.ai {
             // We have a goal, go to it
             AiGoal::Destination{dest} => {
-                let start =  Vec2::from((pos.x, pos.y));
                 let target = Vec2::from((dest.x, dest.y));
-                let lerp_frac = d_r / start.distance(target);
-                let outcome = start.lerp(target, lerp_frac);
-
-                // if no collision take it
-                if !occupied.iter().any(|(p_1, s_1)| collides(&pos, size, p_1, s_1)) {
-                    pos.x = outcome.x;
-                    pos.y = outcome.y;
-                    continue;
-                }
+                steer += seek(here, target) * weights.seek;
+                // Don't overshoot
+                speed = speed.min(here.distance(target) / dt);
             },
 
             // We need to stay here. Do so.
-            AiGoal::None | AiGoal:: Wait => continue,
+            AiGoal::None | AiGoal:: Wait => {
+                velocity.0 = Vec2::ZERO;
+                continue;
+            },
+
+            // Everything else wanders, leaning towards the goal direction
+            _ => {
+                steer += wander(heading.angle, goal_direction(ai.ai), &mut rng) * weights.wander;
+            },
+        }
+
+        // Solid things get steered around, nestmates just get some space
+        let (obstacles, nestmates): (Vec<&Collider>, Vec<&Collider>) = grid.touching(&pos, size, AVOID_DISTANCE)
+            .filter(|c| c.ent != e && groups.interacts(&c.groups))
+            .partition(|c| groups.is_blocked_by(&c.groups));
+        let obstacles: Vec<(Position, Size)> = obstacles.iter().map(|c| (c.pos, c.size)).collect();
+        let nestmates: Vec<(Position, Size)> = nestmates.iter().map(|c| (c.pos, c.size)).collect();
 
-            // Everything else has a movement
-            _ => {},
+        steer += avoid_obstacles(here, size, &obstacles) * weights.avoid_walls;
+        steer += separate(here, size, &nestmates) * weights.separate;
+
+        if steer.length_squared() > 0. {
+            heading.turn_towards(steer, turn_rate * dt);
         }
 
-        // We don't have a destination, random walk
-        let possibles = vec!(
-            Position {y: pos.y + d_r, ..*pos},  // N
-            Position {x: pos.x + d_r, ..*pos},  // E
-            Position {y: pos.y - d_r, ..*pos},  // S
-            Position {x: pos.x - d_r, ..*pos},  // W
-            Position {x: pos.x + d_r, y: pos.y + d_r},  // NE
-            Position {x: pos.x + d_r, y: pos.y - d_r},  // SE
-            Position {x: pos.x - d_r, y: pos.y - d_r},  // SW
-            Position {x: pos.x - d_r, y: pos.y + d_r}   // NW
-        );
-
-        let weights = generate_move_weights(ai.ai);
-
-        let max_width = ARENA_TILE_SIDE * ARENA_WIDTH_TILES as f32;
-        let max_height = ARENA_TILE_SIDE * ARENA_HEIGHT_TILES as f32;
-
-        let possibles: Vec<(Position, i32)> = possibles.into_iter()
-            .zip(weights.into_iter())
-            .filter(|(p, _)| p.x > 0. && p.x < max_width && p.y > 0. && p.y < max_height) // Don't go OOB
-            .filter(|(p, _)| {
-                !occupied.iter().any(|(p_1, s_1)| collides(p, size, p_1, s_1))
-            })
-            .collect();
-
-        let outcome_vec: Vec<Position> = possibles.iter().map(|(p, _)| *p).collect();
-        // short-circuit to avoid the rng
-        if outcome_vec.len() == 1 {
-            pos.x = outcome_vec[0].x;
-            pos.y = outcome_vec[0].y;
-        } else if outcome_vec.len() > 0 {
-            // AI weighted distribution
-            let weight_vec: Vec<i32> = possibles.iter().map(|(_, w)| *w).collect();
-            let dist = WeightedIndex::new(weight_vec).unwrap();
-
-            let mut rng = thread_rng();
-            let newpos  = outcome_vec[dist.sample(&mut rng)];
-            pos.x = newpos.x;
-            pos.y = newpos.y;
+        let step = slide(&pos, heading.direction() * speed * dt, size, groups, &grid);
+        let next = Position { x: pos.x + step.x, y: pos.y + step.y };
+
+        // Don't go OOB
+        if next.x > 0. && next.x < max_width && next.y > 0. && next.y < max_height {
+            pos.x = next.x;
+            pos.y = next.y;
+            velocity.0 = step / dt;
         } else {
-            println!("Help me Step Bro");
+            velocity.0 = Vec2::ZERO;
         }
     }
 }
 
-fn generate_move_weights(ai: AiGoal) -> Vec<i32> {
-    match ai {
-        AiGoal::North =>  vec![16, 4, 1, 4, 8, 1, 1, 8],
-        AiGoal::East =>   vec![4, 16, 4, 1, 8, 8, 1, 1],
-        AiGoal::South =>  vec![1, 4, 16, 4, 1, 8, 8, 1],
-        AiGoal::West =>   vec![4, 1, 4, 16, 1, 1, 8, 8],
-        AiGoal::NE =>     vec![4, 4, 1, 1, 16, 1, 1, 1],
-        AiGoal::SE =>     vec![1, 4, 4, 1, 1, 16, 1, 1],
-        AiGoal::SW =>     vec![1, 1, 4, 4, 1, 1, 16, 1],
-        AiGoal::NW =>     vec![4, 1, 1, 4, 1, 1, 1, 16],
-        AiGoal::Random | AiGoal::Destination{..} => vec![1, 1, 1, 1, 1, 1, 1, 1],
-        _ => panic!("Asked to select move weights for an unsupported goal {:?}", ai),
-    }
+fn goal_direction(ai: AiGoal) -> Option<Vec2> {
+    let dir = match ai {
+        AiGoal::North => Vec2::new(0., 1.),
+        AiGoal::East => Vec2::new(1., 0.),
+        AiGoal::South => Vec2::new(0., -1.),
+        AiGoal::West => Vec2::new(-1., 0.),
+        AiGoal::NE => Vec2::new(1., 1.),
+        AiGoal::SE => Vec2::new(1., -1.),
+        AiGoal::SW => Vec2::new(-1., -1.),
+        AiGoal::NW => Vec2::new(-1., 1.),
+        _ => return None,
+    };
+    Some(dir.normalize())
 }
 
 const ANT_DEATH_COLOR: Color = Color::RED;
 fn ant_coloration(
-    mut ant_sprites: Query<(&mut Sprite, &Health), With<Ant>>,
+    mut ant_sprites: Query<(Option<&mut Sprite>, Option<&mut TextureAtlasSprite>, &Health), With<Ant>>,
 ) {
-    for (mut sprite, health) in ant_sprites.iter_mut() {
+    for (opt_sprite, opt_sheet, health) in ant_sprites.iter_mut() {
         let base = Vec3::from((ANT_COLOR.r(), ANT_COLOR.g(), ANT_COLOR.b()));
         let death = Vec3::from((ANT_DEATH_COLOR.r(), ANT_DEATH_COLOR.g(), ANT_DEATH_COLOR.b()));
         let new = base.lerp(death, 1.0 - health.pct);
-        sprite.color = Color::rgb(new.x, new.y, new.z);
+        let color = Color::rgb(new.x, new.y, new.z);
+        // Plain squares or frames from the sprite sheet, see sprites.rs
+        if let Some(mut sprite) = opt_sprite {
+            sprite.color = color;
+        }
+        if let Some(mut sheet) = opt_sheet {
+            sheet.color = color;
+        }
     }
 }
 
 fn locate_food(
     mut known_food: ResMut<KnownFood>,
+    mut discovered_writer: EventWriter<FoodDiscoveredEvent>,
     mut q: QuerySet<(
-        QueryState<(&Position, &Size, Entity), With<Food>>,
-        QueryState<(&Position, &VisibleRange),  With<Ant>>,
+        QueryState<(&Position, &Size, Entity, &Food)>,
+        QueryState<(Entity, &Position, &VisibleRange),  With<Ant>>,
     )>
 ) {
     let unseen_food: Vec<(Position, Size, Entity)> = q.q0()
         .iter_mut()
-        .filter(|(_, _, e)| {
-            !known_food.locs.contains(e)
+        .filter(|(_, _, e, f)| {
+            !known_food.locs.contains(e) && !f.is_rotten()
         })
-        .map(|(p, s, e)| {
+        .map(|(p, s, e, _)| {
             (*p, *s, e)
         })
         .collect();
 
-    for (ant_p, ant_v) in q.q1().iter() {
+    for (ant, ant_p, ant_v) in q.q1().iter() {
         for (food_p, food_s, ent) in unseen_food.iter() {
-            if collides(ant_p, &ant_v.size, food_p, food_s) {
+            // Two ants can spot the same food at once
+            if collides(ant_p, &ant_v.size, food_p, food_s) && !known_food.locs.contains(ent) {
                 known_food.locs.push(*ent);
+                discovered_writer.send(FoodDiscoveredEvent {
+                    food: *ent,
+                    ant,
+                    at: *food_p,
+                });
                 println!("Added food at {:?}", food_p);
             }
         }
@@ -291,11 +326,11 @@ fn locate_food(
 
 const HEALTH_DEGRADATION_RATE: f32 = 0.1;
 fn health_degrade(
-    time: Res<Time>,
+    clock: Res<SimClock>,
     mut death_writer: EventWriter<AntDeathEvent>,
     mut hitpoints: Query<(&mut Health, &Hunger, Entity)>,
 ) {
-    let dt = time.delta_seconds();
+    let dt = clock.delta_seconds();
     for (mut health, hunger, ent) in hitpoints.iter_mut() {
         if hunger.pct < 0.01 {
             health.pct -= dt * HEALTH_DEGRADATION_RATE;
@@ -312,12 +347,18 @@ fn ant_death_handler(
     locations: Query<&Position, With<Ant>>,
     mut food_spawner: EventWriter<FoodCreateEvent>,
 ) {
+    // A script can kill an ant in the same update it starves
+    let mut dead: HashSet<Entity> = HashSet::new();
     for death in deaths.iter() {
+        if !dead.insert(death.ent) {
+            continue;
+        }
         if let Ok(p) = locations.get(death.ent){
             food_spawner.send(FoodCreateEvent{
                 x: p.x,
                 y: p.y,
                 quantity: 1.0,
+                kind: FoodKind::Protein,
             });
             commands.entity(death.ent).despawn();
         }
@@ -325,14 +366,20 @@ fn ant_death_handler(
 }
 
 
-const HUNGER_DEGRADATION_RATE: f32 = 0.025;
+pub const HUNGER_DEGRADATION_RATE: f32 = 0.025;
 fn hunger_degrade(
-    time: Res<Time>,
-    mut hitpoints: Query<&mut Hunger, Without<AntEating>>,
+    clock: Res<SimClock>,
+    params: Res<AntParams>,
+    mut hitpoints: Query<(&mut Hunger, Option<&Genome>, Option<&Queen>), Without<AntEating>>,
 ) {
-    let dt = time.delta_seconds();
-    for mut hunger in hitpoints.iter_mut() {
-        hunger.pct -= dt * HUNGER_DEGRADATION_RATE;
+    let dt = clock.delta_seconds();
+    for (mut hunger, opt_genome, opt_queen) in hitpoints.iter_mut() {
+        // Genomes are a worker's, the queen goes hungry at the colony's rate
+        let rate = match (opt_genome, opt_queen) {
+            (Some(genome), None) => genome.hunger_rate,
+            _ => params.hunger_rate,
+        };
+        hunger.pct -= dt * rate;
         if hunger.pct < 0. {
             hunger.pct = 0.;
         }
@@ -341,19 +388,13 @@ fn hunger_degrade(
 
 
 #[derive(Component)]
-struct Ant;
+pub struct Ant;
 
 fn spawn_ant(
     mut commands: Commands,
-    ants: Query<&Ant>,
+    spawner: Res<ColonySpawner>,
 ) {
-    let mut current_ants = ants.iter().count();
-    while current_ants < 30 {
-        commands.spawn_bundle(AntBundle::default());
-        current_ants += 1;
-    }
-
-    commands.spawn_bundle(QueenBundle::default());
+    (spawner.0)(&mut commands);
 }
 
 fn start_eat_food(
@@ -395,16 +436,17 @@ fn start_eat_food(
     }
 }
 
-const EAT_RATE: f32 = 0.25;
 fn eat_food(
     mut commands: Commands,
-    time: Res<Time>,
+    clock: Res<SimClock>,
+    params: Res<AntParams>,
+    mut eaten_writer: EventWriter<FoodEatenEvent>,
     mut eating_ants: Query<(&mut Hunger, &AntEating, Entity), With<Ant>>,
-    mut food: Query<&mut Food>,
+    mut food: Query<(&mut Food, &Position)>,
 ) {
-    let dt = time.delta_seconds();
+    let dt = clock.delta_seconds();
     for (mut h, eating, e) in eating_ants.iter_mut() {
-        if let Ok(mut food) = food.get_mut(eating.food_ent) {
+        if let Ok((mut food, food_pos)) = food.get_mut(eating.food_ent) {
             // First food check to avoid double-despawning the food.  We've already
             // despawned it if we see negative before eating any ourselves.
             if food.quantity <= 0. {
@@ -414,8 +456,21 @@ fn eat_food(
             }
 
             if h.pct < 1.0 && food.quantity > 0. {
-                h.pct += dt * EAT_RATE;
-                food.quantity -= dt * EAT_RATE;
+                let amount = (dt * food.kind.eat_rate() * params.eat_rate).min(food.quantity);
+                h.pct += amount * food.nutrition();
+                food.quantity -= amount;
+                eaten_writer.send(FoodEatenEvent {
+                    ant: e,
+                    at: *food_pos,
+                    amount,
+                });
+            }
+
+            // Food that spoiled mid meal isn't worth finishing
+            if food.is_rotten() && food.quantity > 0. {
+                commands.entity(e).insert(AntAI::default());
+                commands.entity(e).remove::<AntEating>();
+                continue;
             }
 
             if food.quantity <= 0. {
@@ -438,43 +493,11 @@ fn eat_food(
     }
 }
 
-// Once ant hunger drops to a certain point, give them a destination
-fn add_food_goal(
-    mut commands: Commands,
-    known_food: Res<KnownFood>,
-    food_pos: Query<&Position, With<Food>>,
-    ants: Query<(Entity, &Position, &Size, &Hunger), (With<Ant>, Without<FindFood>, Without<AntEating>)>,
-) {
-    for (e, ant_pos, ant_size,  hunger) in ants.iter() {
-        if hunger.pct < 0.22 && !known_food.locs.is_empty() {
-            let mut max_dist: f32 = 1000000.;
-            let mut best = known_food.locs[0];
-            for food in known_food.locs.iter() {
-                if let Ok(p) = food_pos.get(*food) {
-                    let new_dist = dist_between(ant_pos, ant_size, p, &crate::arena::Size::square(0.5));
-                    if new_dist < max_dist {
-                        max_dist = new_dist;
-                        best = *food;
-                    }
-                }
-            }
-
-            // Select destination food:
-            if let Ok(food_place) = food_pos.get(best) {
-                commands.entity(e).insert(FindFood);
-                commands.entity(e).insert(AntAI {
-                    ai: AiGoal::Destination {
-                        dest: *food_place,
-                    },
-                    duration: 100000.,
-                });
-            }
-        }
-    }
-}
+// Default AntParams::food_goal_hunger
+pub const FOOD_GOAL_HUNGER: f32 = 0.22;
 
 #[derive(Bundle)]
-struct AntBundle {
+pub struct AntBundle {
     #[bundle]
     sprite: SpriteBundle,
 
@@ -484,7 +507,11 @@ struct AntBundle {
     hunger: Hunger,
     layer: Layer,
     size: Size,
+    collision: CollisionGroups,
+    caste: Caste,
     visibility: VisibleRange,
+    heading: Heading,
+    velocity: Velocity,
 }
 
 impl Default for AntBundle {
@@ -503,16 +530,20 @@ impl Default for AntBundle {
             hunger: Hunger::full(),
             layer: Layer::Main2,
             size: Size::square(0.6),
-            visibility: VisibleRange::new(5.0),
+            collision: CollisionGroups::worker(),
+            caste: Caste::Worker,
+            visibility: VisibleRange::new(ANT_VISION),
+            heading: Heading::random(),
+            velocity: Velocity::default(),
         }
     }
 }
 
 #[derive(Component)]
-struct Queen;
+pub struct Queen;
 
 #[derive(Bundle)]
-struct QueenBundle {
+pub struct QueenBundle {
     #[bundle]
     sprite: SpriteBundle,
 
@@ -523,7 +554,11 @@ struct QueenBundle {
     hunger: Hunger,
     layer: Layer,
     size: Size,
+    collision: CollisionGroups,
+    caste: Caste,
     visibility: VisibleRange,
+    heading: Heading,
+    velocity: Velocity,
 }
 
 impl Default for QueenBundle {
@@ -543,13 +578,17 @@ impl Default for QueenBundle {
             hunger: Hunger::full(),
             layer: Layer::Main2,
             size: Size::square(2.0),
+            collision: CollisionGroups::queen(),
+            caste: Caste::Queen,
             visibility: VisibleRange::new(5.0),
+            heading: Heading::random(),
+            velocity: Velocity::default(),
         }
     }
 }
 
 impl AntBundle {
-    fn new(x: f32, y: f32) -> Self {
+    pub fn new(x: f32, y: f32) -> Self {
         AntBundle {
             position: Position {x, y},
             ..AntBundle::default()
@@ -557,24 +596,33 @@ impl AntBundle {
     }
 }
 
+impl QueenBundle {
+    pub fn new(x: f32, y: f32) -> Self {
+        QueenBundle {
+            position: Position {x, y},
+            ..QueenBundle::default()
+        }
+    }
+}
+
 #[derive(Component)]
-struct Health {
-    pct: f32,
+pub struct Health {
+    pub pct: f32,
 }
 
 impl Health {
-    fn full() -> Health {
+    pub fn full() -> Health {
         Health { pct: 1.0 }
     }
 }
 
 #[derive(Component)]
-struct Hunger {
-    pct: f32,
+pub struct Hunger {
+    pub pct: f32,
 }
 
 impl Hunger {
-    fn full() -> Self {
+    pub fn full() -> Self {
         Hunger {
             pct: 1.0
         }
@@ -596,15 +644,15 @@ impl VisibleRange {
 }
 
 #[derive(Component, Clone, Copy, PartialEq, PartialOrd)]
-struct FindFood;
+pub struct FindFood;
 
 #[derive(Component, PartialEq, PartialOrd)]
-struct AntEating {
-    food_ent: Entity,
+pub struct AntEating {
+    pub food_ent: Entity,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
-enum AiGoal {
+pub enum AiGoal {
     North,
     South,
     East,
@@ -622,9 +670,9 @@ enum AiGoal {
 }
 
 #[derive(Component)]
-struct AntAI {
-    ai: AiGoal,
-    duration: f32,
+pub struct AntAI {
+    pub ai: AiGoal,
+    pub duration: f32,
 }
 
 impl Default for AntAI {
@@ -637,8 +685,8 @@ impl Default for AntAI {
 }
 
 impl AntAI {
-    fn random_move_ai() -> AntAI {
-        let mut rng = thread_rng();
+    pub fn random_move_ai() -> AntAI {
+        let mut rng = SimRng;
         let ai = match rng.gen_range(0, 8) {
             0 => AiGoal::North,
             1 => AiGoal::South,
@@ -49,9 +49,50 @@ pub fn dist_between(p1: &Position, s1: &Size, p2: &Position, s2: &Size) -> f32 {
     v1.distance(v2) - s1.radius() - s2.radius()
 }
 
-// // TODO: CollisionGroups?
-#[derive(Component)]
-pub struct Collides;
+// What an entity is (memberships) and what it bumps into (filter)
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionGroups {
+    pub memberships: u32,
+    pub filter: u32,
+}
+
+impl CollisionGroups {
+    pub const WALL: u32 = 1 << 0;
+    pub const QUEEN: u32 = 1 << 1;
+    pub const WORKER: u32 = 1 << 2;
+    pub const FOOD: u32 = 1 << 3;
+
+    // Groups that can't be pushed aside, only slid along
+    pub const SOLID: u32 = Self::WALL | Self::QUEEN;
+
+    pub fn new(memberships: u32, filter: u32) -> Self {
+        CollisionGroups { memberships, filter }
+    }
+
+    pub fn wall() -> Self {
+        Self::new(Self::WALL, 0)
+    }
+
+    pub fn queen() -> Self {
+        Self::new(Self::QUEEN, Self::WALL)
+    }
+
+    pub fn worker() -> Self {
+        Self::new(Self::WORKER, Self::WALL | Self::QUEEN | Self::WORKER)
+    }
+
+    pub fn food() -> Self {
+        Self::new(Self::FOOD, 0)
+    }
+
+    pub fn interacts(&self, other: &CollisionGroups) -> bool {
+        self.filter & other.memberships != 0
+    }
+
+    pub fn is_blocked_by(&self, other: &CollisionGroups) -> bool {
+        self.interacts(other) && other.memberships & Self::SOLID != 0
+    }
+}
 
 
 #[derive(Default)]
@@ -64,10 +105,42 @@ pub struct ArenaStats {
     arena_height: f32,
 }
 
+impl ArenaStats {
+    pub fn window_size(&self) -> Vec2 {
+        Vec2::new(self.window_width, self.window_height)
+    }
+
+    pub fn arena_size(&self) -> Vec2 {
+        Vec2::new(self.arena_width, self.arena_height)
+    }
+
+    // Arena coordinates to where they're drawn, see position_translation
+    pub fn to_world(&self, pos: &Position) -> Vec2 {
+        Vec2::new(pos.x - self.arena_width / 2., pos.y - self.arena_height / 2.)
+    }
+
+    pub fn to_arena(&self, world: Vec2) -> Position {
+        Position {
+            x: world.x + self.arena_width / 2.,
+            y: world.y + self.arena_height / 2.,
+        }
+    }
+}
+
 pub const ARENA_WIDTH_TILES : u32 = 200;
 pub const ARENA_HEIGHT_TILES : u32 = 100;
 pub const ARENA_TILE_SIDE : f32 = 8.;
 
+// Which arena tile a position falls in, tiles are centred on multiples of the tile side
+pub fn tile_of(pos: &Position) -> Option<(u32, u32)> {
+    let col = (pos.x / ARENA_TILE_SIDE).round();
+    let row = (pos.y / ARENA_TILE_SIDE).round();
+    if col < 0. || row < 0. || col >= ARENA_WIDTH_TILES as f32 || row >= ARENA_HEIGHT_TILES as f32 {
+        return None;
+    }
+    Some((col as u32, row as u32))
+}
+
 pub struct ArenaPlugin;
 
 impl Plugin for ArenaPlugin {
@@ -83,7 +156,6 @@ impl Plugin for ArenaPlugin {
             .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
             .add_startup_system(update_window_stats.label(StartupLabels::Screen))
             .add_startup_system(startup_spawn_arena.after(StartupLabels::Screen))
-            .add_startup_system(setup_camera)
             .add_system(size_scaling)
             .add_system(position_translation)
             .add_system(layer_fixer)
@@ -91,14 +163,18 @@ impl Plugin for ArenaPlugin {
     }
 }
 
+// Headless runs have no window, but the arena is the same size regardless
 fn update_window_stats(
-    windows: Res<Windows>,
+    windows: Option<Res<Windows>>,
     mut screen_builder: ResMut<ArenaStats>
 ) {
-    let window = windows.get_primary().unwrap();
-    if window.height() != screen_builder.window_height || window.width() != screen_builder.window_width {
-        screen_builder.window_height = window.height();
-        screen_builder.window_width = window.width();
+    let (width, height) = match windows.as_ref().and_then(|w| w.get_primary()) {
+        Some(window) => (window.width(), window.height()),
+        None => (0., 0.),
+    };
+    if height != screen_builder.window_height || width != screen_builder.window_width || screen_builder.arena_width == 0. {
+        screen_builder.window_height = height;
+        screen_builder.window_width = width;
 
         let reserved_width = ARENA_WIDTH_TILES as f32 * ARENA_TILE_SIDE;
         let reserved_height = ARENA_HEIGHT_TILES as f32 * ARENA_TILE_SIDE;
@@ -121,10 +197,6 @@ fn layer_to_z(layer: Layer) -> f32 {
     }
 }
 
-fn setup_camera(mut commands: Commands) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-}
-
 fn size_scaling(
     mut q: Query<
         (&Size, &mut Transform),
@@ -0,0 +1,460 @@
+use bevy::prelude::*;
+use crate::clock::SimClock;
+use crate::arena::*;
+use crate::arena::Size;
+use crate::ant::*;
+use crate::config::load_ron;
+use crate::food::Food;
+use crate::genome::Genome;
+use crate::gym::Agent;
+use crate::plugins::RequireExt;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Where the per-caste behaviour files live, relative to the working directory
+const BEHAVIOUR_DIR: &str = "assets/behaviour";
+const BUILTIN_WORKER: &str = include_str!("../assets/behaviour/worker.ron");
+const BUILTIN_QUEEN: &str = include_str!("../assets/behaviour/queen.ron");
+
+// Distances in arena units
+const EAT_REACH: f32 = ARENA_TILE_SIDE;
+const NEST_FAR: f32 = 50. * ARENA_TILE_SIDE;
+const ARRIVAL_DIST: f32 = ARENA_TILE_SIDE;
+// The queen is solid, so home is anywhere close to her
+const NEST_RANGE: f32 = 3. * ARENA_TILE_SIDE;
+const DANGER_RADIUS: f32 = 6. * ARENA_TILE_SIDE;
+const FLEE_DISTANCE: f32 = 10. * ARENA_TILE_SIDE;
+
+// Seconds a death keeps its spot dangerous
+const DANGER_MEMORY: f32 = 30.;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Caste {
+    Worker,
+    Queen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Wander,
+    Forage,
+    Eat,
+    Return,
+    Flee,
+}
+
+// Everything a consideration can look at, each normalised to 0..1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Input {
+    Hunger,
+    Health,
+    KnowsFood,
+    FoodInReach,
+    NestDistance,
+    Danger,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Curve {
+    Linear { slope: f32, offset: f32 },
+    // 1 below the threshold, otherwise 0
+    Below(f32),
+    // 1 above the threshold, otherwise 0
+    Above(f32),
+}
+
+impl Curve {
+    fn eval(&self, x: f32) -> f32 {
+        match *self {
+            Curve::Linear { slope, offset } => (slope * x + offset).clamp(0., 1.),
+            Curve::Below(t) => if x < t { 1. } else { 0. },
+            Curve::Above(t) => if x > t { 1. } else { 0. },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Consideration {
+    pub input: Input,
+    pub curve: Curve,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActionDef {
+    pub action: Action,
+    pub weight: f32,
+    pub considerations: Vec<Consideration>,
+}
+
+impl ActionDef {
+    // forage_hunger stands in for the Hunger Below cutoffs of Forage, see set_hunger_threshold
+    fn score(&self, inputs: &Inputs, forage_hunger: Option<f32>) -> f32 {
+        self.considerations.iter()
+            .fold(self.weight, |acc, c| {
+                let curve = match (self.action, c.input, c.curve, forage_hunger) {
+                    (Action::Forage, Input::Hunger, Curve::Below(_), Some(t)) => Curve::Below(t),
+                    _ => c.curve,
+                };
+                acc * curve.eval(inputs.get(c.input))
+            })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Behaviour {
+    pub wander_duration: f32,
+    // How much better another action must score to interrupt the current one
+    pub switch_margin: f32,
+    pub actions: Vec<ActionDef>,
+}
+
+impl Behaviour {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_ron(path)
+    }
+
+    // Moves the cutoff of every Hunger Below consideration on action
+    pub fn set_hunger_threshold(&mut self, action: Action, threshold: f32) {
+        for def in self.actions.iter_mut().filter(|a| a.action == action) {
+            for c in def.considerations.iter_mut() {
+                if let (Input::Hunger, Curve::Below(_)) = (c.input, c.curve) {
+                    c.curve = Curve::Below(threshold);
+                }
+            }
+        }
+    }
+}
+
+pub struct BehaviourLibrary {
+    pub worker: Behaviour,
+    pub queen: Behaviour,
+}
+
+impl Default for BehaviourLibrary {
+    fn default() -> Self {
+        BehaviourLibrary {
+            worker: Behaviour::from_ron(BUILTIN_WORKER).expect("built in worker behaviour"),
+            queen: Behaviour::from_ron(BUILTIN_QUEEN).expect("built in queen behaviour"),
+        }
+    }
+}
+
+impl BehaviourLibrary {
+    // Files in dir override the built in behaviour for their caste
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut library = BehaviourLibrary::default();
+        for (caste, name) in [(Caste::Worker, "worker.ron"), (Caste::Queen, "queen.ron")] {
+            let path: PathBuf = dir.join(name);
+            if !path.exists() {
+                continue;
+            }
+            match Behaviour::load(&path) {
+                Ok(b) => *library.get_mut(caste) = b,
+                Err(e) => println!("Ignoring behaviour file, using built in {:?}: {}", caste, e),
+            }
+        }
+        library
+    }
+
+    pub fn get(&self, caste: Caste) -> &Behaviour {
+        match caste {
+            Caste::Worker => &self.worker,
+            Caste::Queen => &self.queen,
+        }
+    }
+
+    pub fn get_mut(&mut self, caste: Caste) -> &mut Behaviour {
+        match caste {
+            Caste::Worker => &mut self.worker,
+            Caste::Queen => &mut self.queen,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct CurrentAction {
+    pub action: Option<Action>,
+}
+
+// Places nestmates recently died, fading over DANGER_MEMORY
+#[derive(Default)]
+pub struct DangerZones {
+    zones: Vec<(Vec2, f32)>,
+}
+
+impl DangerZones {
+    pub fn level(&self, at: Vec2) -> f32 {
+        self.zones.iter()
+            .map(|(z, left)| (1. - at.distance(*z) / DANGER_RADIUS).max(0.) * left / DANGER_MEMORY)
+            .fold(0., f32::max)
+    }
+
+    // Direction away from everything dangerous nearby
+    pub fn escape(&self, at: Vec2) -> Vec2 {
+        self.zones.iter()
+            .filter(|(z, _)| at.distance(*z) < DANGER_RADIUS)
+            .map(|(z, _)| (at - *z).normalize_or_zero())
+            .fold(Vec2::ZERO, |acc, v| acc + v)
+            .normalize_or_zero()
+    }
+}
+
+struct Inputs {
+    hunger: f32,
+    health: f32,
+    knows_food: bool,
+    food_in_reach: Option<Entity>,
+    nest_distance: f32,
+    danger: f32,
+}
+
+impl Inputs {
+    fn get(&self, input: Input) -> f32 {
+        let flag = |b: bool| if b { 1. } else { 0. };
+        match input {
+            Input::Hunger => self.hunger,
+            Input::Health => self.health,
+            Input::KnowsFood => flag(self.knows_food),
+            Input::FoodInReach => flag(self.food_in_reach.is_some()),
+            Input::NestDistance => self.nest_distance,
+            Input::Danger => self.danger,
+        }
+    }
+}
+
+pub struct BehaviourPlugin;
+impl Plugin for BehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<KnownFood>("BehaviourPlugin", "AntPlugin")
+            .requires::<Events<AntDeathEvent>>("BehaviourPlugin", "AntPlugin")
+            .requires::<SimClock>("BehaviourPlugin", "ClockPlugin")
+            .requires::<AntParams>("BehaviourPlugin", "AntPlugin")
+            .insert_resource(BehaviourLibrary::load_dir(Path::new(BEHAVIOUR_DIR)))
+            .insert_resource(DangerZones::default())
+            .add_system(apply_ant_params
+                .before(BigPhase::Decide)
+            )
+            .add_system(decide
+                .label(BigPhase::Decide)
+                .after(AntPhase::FindFood)
+            )
+            .add_system(fade_danger)
+            .add_system(record_danger
+                .label(BigPhase::Cleanup)
+                .after(BigPhase::Ambient)
+            );
+    }
+}
+
+// AntParams has the final say on when workers forage
+fn apply_ant_params(
+    params: Res<AntParams>,
+    mut library: ResMut<BehaviourLibrary>,
+) {
+    if params.is_changed() {
+        library.worker.set_hunger_threshold(Action::Forage, params.food_goal_hunger);
+    }
+}
+
+fn decide(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    library: Res<BehaviourLibrary>,
+    danger: Res<DangerZones>,
+    known_food: Res<KnownFood>,
+    food_pos: Query<&Position, With<Food>>,
+    queens: Query<&Position, With<Queen>>,
+    unassigned: Query<Entity, (With<Ant>, Or<(Without<AntAI>, Without<CurrentAction>)>)>,
+    mut ants: Query<
+        (Entity, &Position, &Size, &Caste, &Hunger, &Health, &mut AntAI, &mut CurrentAction, Option<&Genome>),
+        (With<Ant>, Without<AntEating>, Without<FindFood>, Without<Agent>),
+    >,
+) {
+    for ant in unassigned.iter() {
+        commands.entity(ant)
+            .insert(AntAI::default())
+            .insert(CurrentAction::default());
+    }
+
+    let dt = clock.delta_seconds();
+    let nest = queens.iter().next().copied();
+    let known: Vec<(Entity, Position)> = known_food.locs.iter()
+        .filter_map(|e| food_pos.get(*e).ok().map(|p| (*e, *p)))
+        .collect();
+
+    for (e, pos, size, caste, hunger, health, mut ai, mut current, opt_genome) in ants.iter_mut() {
+        let here = Vec2::from((pos.x, pos.y));
+        let behaviour = library.get(*caste);
+
+        let inputs = Inputs {
+            hunger: hunger.pct,
+            health: health.pct,
+            knows_food: !known.is_empty(),
+            food_in_reach: known.iter()
+                .find(|(_, f_p)| dist_between(pos, size, f_p, &Size::square(0.5)) < EAT_REACH)
+                .map(|(f, _)| *f),
+            nest_distance: nest
+                .map(|n| (here.distance(Vec2::from((n.x, n.y))) / NEST_FAR).min(1.))
+                .unwrap_or(0.),
+            danger: danger.level(here),
+        };
+
+        // Tick down the current action and see if it's done
+        let finished = match (current.action, ai.ai) {
+            (_, AiGoal::None) | (None, _) => true,
+            (Some(Action::Wander), AiGoal::Destination{..}) | (Some(Action::Wander), AiGoal::Wait) => false,
+            (Some(Action::Wander), _) => {
+                ai.duration -= dt;
+                ai.duration <= 0.
+            },
+            (Some(Action::Return), AiGoal::Destination{dest}) => {
+                here.distance(Vec2::from((dest.x, dest.y))) < NEST_RANGE
+            },
+            (Some(Action::Flee), AiGoal::Destination{dest}) => {
+                here.distance(Vec2::from((dest.x, dest.y))) < ARRIVAL_DIST
+            },
+            _ => false,
+        };
+
+        // Workers forage by their own genes, queens by the colony's
+        let forage_hunger = match caste {
+            Caste::Worker => opt_genome.map(|g| g.food_goal_hunger),
+            Caste::Queen => None,
+        };
+        let scored: Vec<(Action, f32)> = behaviour.actions.iter()
+            .map(|a| (a.action, a.score(&inputs, forage_hunger)))
+            .collect();
+        let (best, best_score) = scored.iter()
+            .copied()
+            .fold((Action::Wander, 0.), |b, s| if s.1 > b.1 { s } else { b });
+        let current_score = scored.iter()
+            .find(|(a, _)| Some(*a) == current.action)
+            .map(|(_, s)| *s)
+            .unwrap_or(0.);
+
+        let switch = Some(best) != current.action && best_score > current_score + behaviour.switch_margin;
+        if !finished && !switch {
+            continue;
+        }
+
+        // Start the new action, falling back to a wander if it can't
+        let started = match best {
+            Action::Forage => {
+                let nearest = known.iter()
+                    .min_by(|a, b| {
+                        let d_a = dist_between(pos, size, &a.1, &Size::square(0.5));
+                        let d_b = dist_between(pos, size, &b.1, &Size::square(0.5));
+                        d_a.partial_cmp(&d_b).unwrap()
+                    });
+                nearest.map(|(_, f_p)| {
+                    commands.entity(e).insert(FindFood);
+                    *ai = AntAI {
+                        ai: AiGoal::Destination { dest: *f_p },
+                        duration: 100000.,
+                    };
+                })
+            },
+            Action::Eat => {
+                inputs.food_in_reach.map(|food_ent| {
+                    commands.entity(e).insert(AntEating { food_ent });
+                    *ai = AntAI {
+                        ai: AiGoal::Wait,
+                        duration: 0.,
+                    };
+                })
+            },
+            Action::Return => {
+                nest.map(|n| {
+                    *ai = AntAI {
+                        ai: AiGoal::Destination { dest: n },
+                        duration: 100000.,
+                    };
+                })
+            },
+            Action::Flee => {
+                let away = danger.escape(here);
+                if away == Vec2::ZERO {
+                    None
+                } else {
+                    let max_x = ARENA_WIDTH_TILES as f32 * ARENA_TILE_SIDE - 2. * ARENA_TILE_SIDE;
+                    let max_y = ARENA_HEIGHT_TILES as f32 * ARENA_TILE_SIDE - 2. * ARENA_TILE_SIDE;
+                    let target = here + away * FLEE_DISTANCE;
+                    *ai = AntAI {
+                        ai: AiGoal::Destination {
+                            dest: Position {
+                                x: target.x.clamp(2. * ARENA_TILE_SIDE, max_x),
+                                y: target.y.clamp(2. * ARENA_TILE_SIDE, max_y),
+                            },
+                        },
+                        duration: 100000.,
+                    };
+                    Some(())
+                }
+            },
+            Action::Wander => None,
+        };
+
+        current.action = if started.is_some() {
+            Some(best)
+        } else {
+            *ai = AntAI::random_move_ai();
+            ai.duration = match (caste, opt_genome) {
+                (Caste::Worker, Some(genome)) => genome.wander_duration,
+                _ => behaviour.wander_duration,
+            };
+            Some(Action::Wander)
+        };
+    }
+}
+
+fn record_danger(
+    mut danger: ResMut<DangerZones>,
+    mut deaths: EventReader<AntDeathEvent>,
+    locations: Query<&Position, With<Ant>>,
+) {
+    for death in deaths.iter() {
+        if let Ok(p) = locations.get(death.ent) {
+            danger.zones.push((Vec2::from((p.x, p.y)), DANGER_MEMORY));
+        }
+    }
+}
+
+fn fade_danger(
+    clock: Res<SimClock>,
+    mut danger: ResMut<DangerZones>,
+) {
+    let dt = clock.delta_seconds();
+    for zone in danger.zones.iter_mut() {
+        zone.1 -= dt;
+    }
+    danger.zones.retain(|(_, left)| *left > 0.);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves() {
+        let linear = Curve::Linear { slope: -2., offset: 1.5 };
+        assert_eq!(linear.eval(0.), 1.);
+        assert_eq!(linear.eval(0.5), 0.5);
+        assert_eq!(linear.eval(1.), 0.);
+        assert_eq!(Curve::Below(0.3).eval(0.2), 1.);
+        assert_eq!(Curve::Below(0.3).eval(0.3), 0.);
+        assert_eq!(Curve::Above(0.3).eval(0.4), 1.);
+        assert_eq!(Curve::Above(0.3).eval(0.3), 0.);
+    }
+
+    #[test]
+    fn builtin_behaviours_load() {
+        let library = BehaviourLibrary::default();
+        assert!(library.get(Caste::Worker).actions.iter().any(|a| a.action == Action::Forage));
+        assert!(!library.get(Caste::Queen).actions.is_empty());
+    }
+}
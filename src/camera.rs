@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use bevy::asset::LoadState;
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use crate::arena::*;
+use crate::ant::Ant;
+use crate::plugins::RequireExt;
+
+const MIN_ZOOM: f32 = 0.2;
+// Fraction of the zoom per wheel line
+const ZOOM_STEP: f32 = 0.1;
+// Screen pixels per second, scaled by zoom
+const PAN_SPEED: f32 = 600.;
+const DRAG_BUTTON: MouseButton = MouseButton::Right;
+// Shipped in assets/fonts with its licence
+const UI_FONT: &str = "fonts/DejaVuSansMono.ttf";
+
+#[derive(Component)]
+pub struct MainCamera;
+
+// Entity the player picked, if any
+#[derive(Default)]
+pub struct Selected(pub Option<Entity>);
+
+pub struct UiFont(pub Handle<Font>);
+
+#[derive(Default)]
+pub struct CameraControl {
+    pub follow: bool,
+}
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+enum CameraPhase {
+    Input,
+    Follow,
+}
+
+pub struct CameraPlugin;
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<ArenaStats>("CameraPlugin", "ArenaPlugin")
+            .requires::<AssetServer>("CameraPlugin", "DefaultPlugins")
+            .requires::<Input<KeyCode>>("CameraPlugin", "DefaultPlugins")
+            .init_resource::<Selected>()
+            .init_resource::<CameraControl>()
+            .add_startup_system(setup_camera)
+            .add_system(check_font)
+            .add_system(camera_zoom.label(CameraPhase::Input))
+            .add_system(camera_pan.label(CameraPhase::Input))
+            .add_system(cycle_selection.label(CameraPhase::Input))
+            .add_system(camera_follow
+                .label(CameraPhase::Follow)
+                .after(CameraPhase::Input)
+            )
+            .add_system(clamp_camera.after(CameraPhase::Follow));
+    }
+}
+
+fn setup_camera(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands.insert_resource(UiFont(asset_server.load(UI_FONT)));
+}
+
+// Text in a font that didn't load never draws, so say why rather than show nothing
+fn check_font(
+    asset_server: Res<AssetServer>,
+    font: Option<Res<UiFont>>,
+) {
+    if let Some(font) = font {
+        if asset_server.get_load_state(&font.0) == LoadState::Failed {
+            panic!("Couldn't load the UI font assets/{}, run from the repository root", UI_FONT);
+        }
+    }
+}
+
+// Where the cursor is in world space, None when it's outside the window
+pub fn cursor_to_world(window: &Window, camera: &Transform) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let from_centre = cursor - Vec2::new(window.width(), window.height()) / 2.;
+    Some(camera.translation.truncate() + from_centre * camera.scale.truncate())
+}
+
+// Zoomed all the way out the whole arena just fits in the window
+fn max_zoom(screen: &ArenaStats) -> f32 {
+    let window = screen.window_size();
+    let arena = screen.arena_size();
+    if window.x <= 0. || window.y <= 0. {
+        return 1.;
+    }
+    (arena.x / window.x).max(arena.y / window.y).max(MIN_ZOOM)
+}
+
+fn camera_zoom(
+    screen: Res<ArenaStats>,
+    mut wheel: EventReader<MouseWheel>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    let lines: f32 = wheel.iter()
+        .map(|w| match w.unit {
+            MouseScrollUnit::Line => w.y,
+            MouseScrollUnit::Pixel => w.y / 100.,
+        })
+        .sum();
+    if lines == 0. {
+        return;
+    }
+
+    for mut transform in cameras.iter_mut() {
+        let zoom = (transform.scale.x * (1. - lines * ZOOM_STEP)).clamp(MIN_ZOOM, max_zoom(&screen));
+        transform.scale = Vec3::new(zoom, zoom, transform.scale.z);
+    }
+}
+
+fn camera_pan(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut control: ResMut<CameraControl>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    let mut pan = Vec2::ZERO;
+
+    let dt = time.delta_seconds();
+    if keys.pressed(KeyCode::W) || keys.pressed(KeyCode::Up) {
+        pan.y += PAN_SPEED * dt;
+    }
+    if keys.pressed(KeyCode::S) || keys.pressed(KeyCode::Down) {
+        pan.y -= PAN_SPEED * dt;
+    }
+    if keys.pressed(KeyCode::D) || keys.pressed(KeyCode::Right) {
+        pan.x += PAN_SPEED * dt;
+    }
+    if keys.pressed(KeyCode::A) || keys.pressed(KeyCode::Left) {
+        pan.x -= PAN_SPEED * dt;
+    }
+
+    // Drag the world along with the mouse, motion is in screen space with y down
+    let dragged = motion.iter().fold(Vec2::ZERO, |acc, m| acc + m.delta);
+    if buttons.pressed(DRAG_BUTTON) {
+        pan += Vec2::new(-dragged.x, dragged.y);
+    }
+
+    if pan == Vec2::ZERO {
+        return;
+    }
+
+    // Moving by hand stops following
+    control.follow = false;
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x += pan.x * transform.scale.x;
+        transform.translation.y += pan.y * transform.scale.y;
+    }
+}
+
+// Tab picks the next ant, F toggles following it
+fn cycle_selection(
+    keys: Res<Input<KeyCode>>,
+    mut selected: ResMut<Selected>,
+    mut control: ResMut<CameraControl>,
+    ants: Query<Entity, With<Ant>>,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        let all: Vec<Entity> = ants.iter().collect();
+        if !all.is_empty() {
+            let next = selected.0
+                .and_then(|s| all.iter().position(|e| *e == s))
+                .map(|i| (i + 1) % all.len())
+                .unwrap_or(0);
+            selected.0 = Some(all[next]);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::F) {
+        control.follow = !control.follow && selected.0.is_some();
+    }
+}
+
+fn camera_follow(
+    screen: Res<ArenaStats>,
+    mut selected: ResMut<Selected>,
+    mut control: ResMut<CameraControl>,
+    positions: Query<&Position>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !control.follow {
+        return;
+    }
+
+    let target = match selected.0.map(|e| positions.get(e)) {
+        Some(Ok(p)) => screen.to_world(p),
+        // Selection died or went away
+        _ => {
+            selected.0 = None;
+            control.follow = false;
+            return;
+        },
+    };
+
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = target.x;
+        transform.translation.y = target.y;
+    }
+}
+
+// Keep the view over the arena, centring it if it's zoomed out past the edges
+fn clamp_camera(
+    screen: Res<ArenaStats>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    let half_arena = screen.arena_size() / 2.;
+    let half_window = screen.window_size() / 2.;
+
+    for mut transform in cameras.iter_mut() {
+        let half_view = half_window * transform.scale.truncate();
+        let limit = (half_arena - half_view).max(Vec2::ZERO);
+        transform.translation.x = transform.translation.x.clamp(-limit.x, limit.x);
+        transform.translation.y = transform.translation.y.clamp(-limit.y, limit.y);
+    }
+}
@@ -0,0 +1,411 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use bevy::core::DefaultTaskPoolOptions;
+use bevy::winit::WinitConfig;
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::clock::SimClock;
+use crate::config::SimConfig;
+use crate::headless::{self, Outcome, RunReport};
+use crate::heatmap::HeatmapSettings;
+use crate::evolve::{self, EvolveSpec};
+use crate::genome::Genome;
+use crate::gym;
+use crate::map::{self, MapDef};
+use crate::remote::RemotePlugin;
+use crate::scenario::Scenario;
+use crate::sweep::{self, SweepSpec};
+
+// Exit codes
+pub const SURVIVED: i32 = 0;
+pub const COLONY_DIED: i32 = 1;
+pub const FAILED: i32 = 2;
+pub const OBJECTIVES_FAILED: i32 = 3;
+
+const RUNS_DIR: &str = "runs";
+
+#[derive(Parser)]
+#[clap(name = "antfarm", about = "Ant colony simulation")]
+pub struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(flatten)]
+    sim: SimArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open the window (the default)
+    Run,
+    /// Simulate without a window and write run.ron and stats.csv
+    Headless,
+    /// Simulate without a window as fast as possible and report ticks per second
+    Bench,
+    /// Run again from the run.ron of an earlier run
+    Replay {
+        file: PathBuf,
+        #[clap(long)]
+        headless: bool,
+    },
+    /// Run a grid of ant parameters over several seeds, in parallel
+    Sweep {
+        file: PathBuf,
+        /// Runs at once, one per core if not given
+        #[clap(long)]
+        jobs: Option<usize>,
+    },
+    /// Evolve worker genomes by headless runs, saving the best to best.ron
+    Evolve {
+        file: PathBuf,
+        /// Runs at once, one per core if not given
+        #[clap(long)]
+        jobs: Option<usize>,
+    },
+    /// Play a scenario by name in assets/scenarios, or from a file
+    Scenario {
+        name: String,
+        #[clap(long)]
+        headless: bool,
+    },
+    /// Run every scenario in assets/scenarios headless and check their objectives are met
+    Scenarios {
+        /// Runs at once, one per core if not given
+        #[clap(long)]
+        jobs: Option<usize>,
+    },
+    /// Serve a reinforcement learning environment to a local trainer
+    Gym {
+        #[clap(long, default_value_t = 5555)]
+        port: u16,
+    },
+    /// Write a random map
+    GenMap {
+        #[clap(long, default_value = "generated")]
+        name: String,
+        #[clap(long, default_value_t = 12)]
+        obstacles: usize,
+        #[clap(long, default_value_t = 3)]
+        patches: usize,
+    },
+}
+
+#[derive(Args)]
+struct SimArgs {
+    /// Seed for the simulation, random if not given
+    #[clap(long, global = true)]
+    seed: Option<u64>,
+    /// Map name in assets/maps, or a path to a map file; ANTFARM_MAP if not given
+    #[clap(long, global = true)]
+    map: Option<String>,
+    /// RON file with a SimConfig; other options override it
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    /// Worker ants to start with
+    #[clap(long, global = true)]
+    ants: Option<usize>,
+    /// Piles of food to start with, instead of the map's own
+    #[clap(long, global = true)]
+    food: Option<usize>,
+    /// Worker genome file, such as the best.ron written by evolve
+    #[clap(long, global = true)]
+    genome: Option<PathBuf>,
+    /// Rhai script name in assets/scripts, or a path to a script file
+    #[clap(long, global = true)]
+    script: Option<String>,
+    /// Stop after this many ticks
+    #[clap(long, global = true)]
+    ticks: Option<u64>,
+    /// Where run output, heatmaps and generated maps go
+    #[clap(long, global = true)]
+    out: Option<PathBuf>,
+    /// Accept remote control connections on this local port
+    #[clap(long, global = true)]
+    remote: Option<u16>,
+}
+
+impl SimArgs {
+    fn config(&self) -> Result<SimConfig, String> {
+        let config = match &self.config {
+            Some(path) => SimConfig::load(path)?,
+            None => SimConfig::default(),
+        };
+        self.apply(config)
+    }
+
+    // The options given on the command line, on top of config
+    fn apply(&self, mut config: SimConfig) -> Result<SimConfig, String> {
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+        if self.map.is_some() {
+            config.map = self.map.clone();
+        } else if config.map.is_none() {
+            // Recorded in run.ron, so replays don't depend on the environment
+            config.map = map::env_map_name();
+        }
+        if let Some(ants) = self.ants {
+            config.ants = ants;
+        }
+        if self.food.is_some() {
+            config.food = self.food;
+        }
+        if self.ticks.is_some() {
+            config.ticks = self.ticks;
+        }
+        if self.script.is_some() {
+            config.script = self.script.clone();
+        }
+        if let Some(path) = &self.genome {
+            config.genome = Some(Genome::load(path)?);
+        }
+        Ok(config)
+    }
+
+    fn out_dir(&self) -> PathBuf {
+        self.out.clone().unwrap_or_else(|| {
+            let stamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            Path::new(RUNS_DIR).join(format!("{}", stamp))
+        })
+    }
+}
+
+// Does what the command line asked, returning the exit code
+pub fn run(cli: Cli) -> Result<i32, String> {
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            let mut config = cli.sim.config()?;
+            config.seed_rng();
+            gui(&config, cli.sim.out.as_deref(), cli.sim.remote, false)
+        },
+        Command::Headless => {
+            let mut config = cli.sim.config()?;
+            config.seed_rng();
+            let report = headless_run(&config, &cli.sim.out_dir(), cli.sim.remote)?;
+            println!("{}", report);
+            Ok(exit_code(&report))
+        },
+        Command::Bench => {
+            let mut config = cli.sim.config()?;
+            config.seed_rng();
+            bench(&config, cli.sim.remote)
+        },
+        Command::Replay { file, headless } => {
+            let mut config = SimConfig::load(&file)?;
+            if config.seed.is_none() {
+                return Err(format!("{} has no seed to replay", file.display()));
+            }
+            config.seed_rng();
+            if headless {
+                let report = headless_run(&config, &cli.sim.out_dir(), cli.sim.remote)?;
+                println!("{}", report);
+                Ok(exit_code(&report))
+            } else {
+                gui(&config, cli.sim.out.as_deref(), cli.sim.remote, true)
+            }
+        },
+        Command::Sweep { file, jobs } => {
+            let spec = SweepSpec::load(&file)?;
+            let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+            let out = cli.sim.out_dir();
+            let rows = sweep::run_sweep(&spec, &exe, &out, jobs.unwrap_or_else(default_jobs))?;
+
+            let path = out.join("summary.csv");
+            sweep::write_summary(&rows, &path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            println!("{:>8} {:>6} {:>6} {:>9} {:>9} {:>6} {:>10}", "hunger", "eat", "goal", "survived", "seconds", "peak", "efficiency");
+            for row in rows.iter() {
+                println!(
+                    "{:>8.4} {:>6.2} {:>6.2} {:>6}/{:<2} {:>9.0} {:>6.1} {:>10.1}",
+                    row.params.hunger_rate, row.params.eat_rate, row.params.food_goal_hunger,
+                    row.survived, row.runs, row.survival_seconds, row.peak_population, row.food_efficiency,
+                );
+            }
+            println!("Summary written to {}", path.display());
+            Ok(SURVIVED)
+        },
+        Command::Evolve { file, jobs } => {
+            let spec = EvolveSpec::load(&file)?;
+            let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+            let out = cli.sim.out_dir();
+            let (best, fitness) = evolve::evolve(&spec, &exe, &out, jobs.unwrap_or_else(default_jobs))?;
+            println!("Best fitness {:.3}: {:?}", fitness, best);
+            println!("Saved to {}, run it with --genome", out.join("best.ron").display());
+            Ok(SURVIVED)
+        },
+        Command::Scenario { name, headless } => {
+            let scenario = Scenario::resolve(&name)?;
+            println!("{}: {}", scenario.name, scenario.description);
+            let mut config = cli.sim.apply(scenario.config)?;
+            config.seed_rng();
+            if headless {
+                let report = headless_run(&config, &cli.sim.out_dir(), cli.sim.remote)?;
+                println!("{}", report);
+                Ok(exit_code(&report))
+            } else {
+                gui(&config, cli.sim.out.as_deref(), cli.sim.remote, false)
+            }
+        },
+        Command::Scenarios { jobs } => {
+            let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+            let out = cli.sim.out_dir();
+            let scenarios = Scenario::builtin()?;
+            let runs = scenarios.iter()
+                .map(|(path, scenario)| {
+                    let stem = path.file_stem().map_or(scenario.name.clone(), |s| s.to_string_lossy().into_owned());
+                    (scenario.config.clone(), out.join(stem))
+                })
+                .collect();
+            let results = sweep::run_parallel(&exe, runs, jobs.unwrap_or_else(default_jobs));
+
+            let (mut lost, mut broken) = (false, false);
+            for ((_, scenario), result) in scenarios.iter().zip(results.iter()) {
+                match result {
+                    Ok(report) => {
+                        println!("{:<24} {:?} after {:.0}s", scenario.name, report.outcome, report.sim_seconds);
+                        lost |= exit_code(report) != SURVIVED;
+                    },
+                    Err(e) => {
+                        println!("{:<24} {}", scenario.name, e);
+                        broken = true;
+                    },
+                }
+            }
+            Ok(if broken {
+                FAILED
+            } else if lost {
+                OBJECTIVES_FAILED
+            } else {
+                SURVIVED
+            })
+        },
+        Command::Gym { port } => {
+            gym::serve(cli.sim.config()?, port)?;
+            Ok(SURVIVED)
+        },
+        Command::GenMap { name, obstacles, patches } => {
+            if let Some(seed) = cli.sim.seed {
+                crate::rng::seed(seed);
+            }
+            let map = MapDef::generate(&name, obstacles, patches);
+            let path = cli.sim.out.unwrap_or_else(|| PathBuf::from(format!("{}.ron", name)));
+            std::fs::write(&path, map.to_ron()?).map_err(|e| format!("{}: {}", path.display(), e))?;
+            println!("Map written to {}", path.display());
+            Ok(SURVIVED)
+        },
+    }
+}
+
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+fn exit_code(report: &RunReport) -> i32 {
+    match report.outcome {
+        Outcome::Survived => SURVIVED,
+        Outcome::ColonyDied => COLONY_DIED,
+        Outcome::ObjectivesMet => SURVIVED,
+        Outcome::ObjectivesFailed => OBJECTIVES_FAILED,
+    }
+}
+
+fn headless_run(config: &SimConfig, out: &Path, remote: Option<u16>) -> Result<RunReport, String> {
+    let mut app = headless::headless_app(config)?;
+    if let Some(port) = remote {
+        app.add_plugin(RemotePlugin::bind(port)?);
+    }
+    let report = headless::run(&mut app, config.ticks.unwrap_or(headless::DEFAULT_TICKS));
+    headless::save_run(&app.world, config, &report, out)?;
+    println!("Run written to {}", out.display());
+    Ok(report)
+}
+
+fn bench(config: &SimConfig, remote: Option<u16>) -> Result<i32, String> {
+    let mut app = headless::headless_app(config)?;
+    if let Some(port) = remote {
+        app.add_plugin(RemotePlugin::bind(port)?);
+    }
+    let start = Instant::now();
+    let report = headless::run(&mut app, config.ticks.unwrap_or(headless::DEFAULT_TICKS));
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{} ticks in {:.2}s, {:.0} ticks/s, {:.1}x real time",
+        report.ticks,
+        seconds,
+        report.ticks as f64 / seconds,
+        report.sim_seconds / seconds,
+    );
+    Ok(exit_code(&report))
+}
+
+// Counts the window's ticks and closes it once the limit is reached
+struct TickLimit {
+    ticks: u64,
+    limit: Option<u64>,
+}
+
+fn tick_limit(
+    mut limit: ResMut<TickLimit>,
+    mut exit: EventWriter<AppExit>,
+) {
+    limit.ticks += 1;
+    if limit.limit.map_or(false, |l| limit.ticks >= l) {
+        exit.send(AppExit);
+    }
+}
+
+// The run's report, taken on its last frame since App::run consumes the world
+#[derive(Clone, Default)]
+struct FinalReport(Arc<Mutex<Option<RunReport>>>);
+
+fn record_report(world: &mut World) {
+    let exiting = world.get_resource::<Events<AppExit>>()
+        .map_or(false, |events| events.iter_current_update_events().next().is_some());
+    if !exiting {
+        return;
+    }
+    let ticks = world.get_resource::<TickLimit>().map_or(0, |l| l.ticks);
+    let report = headless::finish(world, ticks);
+    if let Some(slot) = world.get_resource::<FinalReport>() {
+        *slot.0.lock().unwrap() = Some(report);
+    }
+}
+
+// A replay steps the clock by fixed ticks on one thread, like the headless run it came from
+fn gui(config: &SimConfig, out: Option<&Path>, remote: Option<u16>, replay: bool) -> Result<i32, String> {
+    let mut app = App::new();
+    if replay {
+        app
+            .insert_resource(DefaultTaskPoolOptions::with_num_threads(1))
+            .insert_resource(SimClock::fixed(config.tick_seconds));
+    }
+    if let Some(out) = out {
+        app.insert_resource(HeatmapSettings {
+            export_dir: out.to_path_buf(),
+            ..Default::default()
+        });
+    }
+    app
+        // So the exit code can be worked out once the window closes
+        .insert_resource(WinitConfig { return_from_run: true })
+        .insert_resource(TickLimit { ticks: 0, limit: config.ticks })
+        .add_system(tick_limit)
+        .add_plugins(config.plugins()?)
+        .add_plugins(DefaultPlugins);
+    if let Some(port) = remote {
+        app.add_plugin(RemotePlugin::bind(port)?);
+    }
+    let final_report = FinalReport::default();
+    app
+        .insert_resource(final_report.clone())
+        .add_system_to_stage(CoreStage::Last, record_report.exclusive_system());
+    app.run();
+    let report = final_report.0.lock().unwrap().take();
+    let report = report.ok_or_else(|| "The window closed without a report".to_string())?;
+    println!("{}", report);
+    Ok(exit_code(&report))
+}
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 16.;
+
+// Simulation time, which can run faster or slower than the wall clock or stop.
+// Everything that ages, moves or eats should tick off this rather than Time.
+pub struct SimClock {
+    delta: f32,
+    elapsed: f64,
+    pub speed: f32,
+    pub paused: bool,
+    // Seconds per update regardless of the wall clock, for headless runs
+    pub fixed_step: Option<f32>,
+    // Updates still to run while paused
+    pending_steps: u32,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        SimClock {
+            delta: 0.,
+            elapsed: 0.,
+            speed: 1.,
+            paused: false,
+            fixed_step: None,
+            pending_steps: 0,
+        }
+    }
+}
+
+impl SimClock {
+    pub fn fixed(step: f32) -> Self {
+        SimClock {
+            fixed_step: Some(step),
+            ..SimClock::default()
+        }
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta
+    }
+
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f32(self.delta)
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    // Run this many more updates even though paused
+    pub fn step(&mut self, ticks: u32) {
+        self.pending_steps += ticks;
+    }
+}
+
+pub struct ClockPlugin;
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SimClock>()
+            .add_system_to_stage(CoreStage::PreUpdate, advance_clock);
+    }
+}
+
+fn advance_clock(
+    time: Res<Time>,
+    mut clock: ResMut<SimClock>,
+) {
+    let stepping = clock.paused && clock.pending_steps > 0;
+    if stepping {
+        clock.pending_steps -= 1;
+    }
+    clock.delta = if clock.paused && !stepping {
+        0.
+    } else {
+        clock.fixed_step.unwrap_or_else(|| time.delta_seconds()) * clock.speed
+    };
+    clock.elapsed += clock.delta as f64;
+}
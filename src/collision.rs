@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+use crate::arena::*;
+use crate::arena::Size;
+use crate::ant::{Ant, BigPhase};
+
+// Side of one broadphase cell, in arena units
+pub const GRID_CELL: f32 = 4. * ARENA_TILE_SIDE;
+// Nothing that collides is wider than this (the queen is the biggest)
+const MAX_COLLIDER_RADIUS: f32 = 2. * ARENA_TILE_SIDE;
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum CollisionPhase {
+    Grid,
+    Resolve,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub ent: Entity,
+    pub pos: Position,
+    pub size: Size,
+    pub groups: CollisionGroups,
+}
+
+// Uniform grid of colliders so neighbours don't need a scan of every wall
+pub struct SpatialGrid {
+    pub cols: usize,
+    pub rows: usize,
+    cells: Vec<Vec<Collider>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        let cols = (ARENA_WIDTH_TILES as f32 * ARENA_TILE_SIDE / GRID_CELL).ceil() as usize;
+        let rows = (ARENA_HEIGHT_TILES as f32 * ARENA_TILE_SIDE / GRID_CELL).ceil() as usize;
+        SpatialGrid {
+            cols,
+            rows,
+            cells: vec![Vec::new(); cols * rows],
+        }
+    }
+}
+
+impl SpatialGrid {
+    fn cell_of(&self, x: f32, y: f32) -> (usize, usize) {
+        let col = ((x / GRID_CELL).floor().max(0.) as usize).min(self.cols - 1);
+        let row = ((y / GRID_CELL).floor().max(0.) as usize).min(self.rows - 1);
+        (col, row)
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+    }
+
+    pub fn insert(&mut self, collider: Collider) {
+        let (col, row) = self.cell_of(collider.pos.x, collider.pos.y);
+        let cols = self.cols;
+        self.cells[row * cols + col].push(collider);
+    }
+
+    pub fn count(&self, col: usize, row: usize) -> usize {
+        self.cells[row * self.cols + col].len()
+    }
+
+    // Everything whose centre is in a cell touching the square of side 2 * range around pos
+    pub fn near(&self, pos: &Position, range: f32) -> impl Iterator<Item = &Collider> {
+        let (min_col, min_row) = self.cell_of(pos.x - range, pos.y - range);
+        let (max_col, max_row) = self.cell_of(pos.x + range, pos.y + range);
+        let cols = self.cols;
+        (min_row..=max_row)
+            .flat_map(move |row| (min_col..=max_col).map(move |col| row * cols + col))
+            .flat_map(move |i| self.cells[i].iter())
+    }
+
+    // Colliders that could touch something of this size at pos
+    pub fn touching(&self, pos: &Position, size: &Size, extra: f32) -> impl Iterator<Item = &Collider> {
+        self.near(pos, size.radius() + MAX_COLLIDER_RADIUS + extra)
+    }
+}
+
+pub struct CollisionPlugin;
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SpatialGrid>()
+            .add_system(update_spatial_grid
+                .label(CollisionPhase::Grid)
+                .before(BigPhase::Move)
+            )
+            .add_system(resolve_overlaps
+                .label(CollisionPhase::Resolve)
+                .after(BigPhase::Move)
+                .before(BigPhase::Act)
+            );
+    }
+}
+
+fn rebuild<'a>(grid: &mut SpatialGrid, colliders: impl Iterator<Item = (Entity, &'a Position, &'a Size, &'a CollisionGroups)>) {
+    grid.clear();
+    for (ent, pos, size, groups) in colliders {
+        grid.insert(Collider {
+            ent,
+            pos: *pos,
+            size: *size,
+            groups: *groups,
+        });
+    }
+}
+
+fn update_spatial_grid(
+    mut grid: ResMut<SpatialGrid>,
+    colliders: Query<(Entity, &Position, &Size, &CollisionGroups)>,
+) {
+    rebuild(&mut grid, colliders.iter());
+}
+
+// Try step from `from`, sliding along whatever solid thing is in the way
+pub fn slide(from: &Position, step: Vec2, size: &Size, groups: &CollisionGroups, grid: &SpatialGrid) -> Vec2 {
+    let start = Vec2::from((from.x, from.y));
+
+    // Moving out of something we already overlap is always allowed
+    let blocker = |s: Vec2| -> Option<Collider> {
+        let next = Position { x: from.x + s.x, y: from.y + s.y };
+        grid.touching(from, size, s.length())
+            .filter(|c| groups.is_blocked_by(&c.groups))
+            .find(|c| {
+                collides(&next, size, &c.pos, &c.size)
+                    && dist_between(&next, size, &c.pos, &c.size) < dist_between(from, size, &c.pos, &c.size)
+            })
+            .copied()
+    };
+
+    let wall = match blocker(step) {
+        None => return step,
+        Some(c) => c,
+    };
+
+    // Drop the part of the step going into the obstacle
+    let normal = (start - Vec2::from((wall.pos.x, wall.pos.y))).normalize_or_zero();
+    let into = step.dot(normal);
+    if into < 0. {
+        let tangent = step - normal * into;
+        if blocker(tangent).is_none() {
+            return tangent;
+        }
+    }
+
+    for s in [Vec2::new(step.x, 0.), Vec2::new(0., step.y)] {
+        if blocker(s).is_none() {
+            return s;
+        }
+    }
+    Vec2::ZERO
+}
+
+// Push apart anything that ended up overlapping after movement. Workers share the
+// push between them, anything solid pushes the other all the way out.
+fn resolve_overlaps(
+    mut grid: ResMut<SpatialGrid>,
+    mut q: QuerySet<(
+        QueryState<(Entity, &Position, &Size, &CollisionGroups)>,
+        QueryState<&mut Position, With<Ant>>,
+    )>,
+) {
+    rebuild(&mut grid, q.q0().iter());
+
+    let mut pushes: Vec<(Entity, Vec2)> = Vec::new();
+    for (e, p, s, g) in q.q0().iter() {
+        // Static
+        if g.filter == 0 {
+            continue;
+        }
+
+        let here = Vec2::from((p.x, p.y));
+        let mut push = Vec2::ZERO;
+        for other in grid.touching(p, s, 0.) {
+            if other.ent == e || !g.interacts(&other.groups) {
+                continue;
+            }
+            let overlap = s.radius() + other.size.radius() - here.distance(Vec2::from((other.pos.x, other.pos.y)));
+            if overlap <= 0. {
+                continue;
+            }
+
+            let mut normal = (here - Vec2::from((other.pos.x, other.pos.y))).normalize_or_zero();
+            if normal == Vec2::ZERO {
+                // Stacked exactly, spread out by entity so the pair split different ways
+                let angle = e.id() as f32 * 2.4;
+                normal = Vec2::new(angle.cos(), angle.sin());
+            }
+            let share = if g.is_blocked_by(&other.groups) { 1.0 } else { 0.5 };
+            push += normal * overlap * share;
+        }
+
+        if push != Vec2::ZERO {
+            pushes.push((e, push));
+        }
+    }
+
+    let max_width = ARENA_TILE_SIDE * ARENA_WIDTH_TILES as f32;
+    let max_height = ARENA_TILE_SIDE * ARENA_HEIGHT_TILES as f32;
+
+    let mut movers = q.q1();
+    for (e, push) in pushes.into_iter() {
+        if let Ok(mut pos) = movers.get_mut(e) {
+            // Don't push anything OOB, the way ant_movement won't walk there
+            let x = pos.x + push.x;
+            if x > 0. && x < max_width {
+                pos.x = x;
+            }
+            let y = pos.y + push.y;
+            if y > 0. && y < max_height {
+                pos.y = y;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with(pos: Position, groups: CollisionGroups) -> SpatialGrid {
+        let mut grid = SpatialGrid::default();
+        grid.insert(Collider {
+            ent: Entity::new(0),
+            pos,
+            size: Size::square(1.),
+            groups,
+        });
+        grid
+    }
+
+    fn worker_at(x: f32, y: f32, step: Vec2, grid: &SpatialGrid) -> Vec2 {
+        slide(&Position { x, y }, step, &Size::square(1.), &CollisionGroups::worker(), grid)
+    }
+
+    #[test]
+    fn clear_step_is_kept() {
+        let grid = SpatialGrid::default();
+        assert_eq!(worker_at(28., 40., Vec2::new(4., 3.), &grid), Vec2::new(4., 3.));
+    }
+
+    #[test]
+    fn straight_into_a_wall_stops() {
+        let grid = grid_with(Position { x: 40., y: 40. }, CollisionGroups::wall());
+        assert_eq!(worker_at(28., 40., Vec2::new(4., 0.), &grid), Vec2::ZERO);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let grid = grid_with(Position { x: 40., y: 40. }, CollisionGroups::wall());
+        let step = worker_at(28., 40., Vec2::new(4., 3.), &grid);
+        assert!(step.x.abs() < 1e-5, "{:?}", step);
+        assert!((step.y - 3.).abs() < 1e-5, "{:?}", step);
+    }
+
+    #[test]
+    fn can_move_out_of_an_overlap() {
+        let grid = grid_with(Position { x: 40., y: 40. }, CollisionGroups::wall());
+        assert_eq!(worker_at(34., 40., Vec2::new(-4., 0.), &grid), Vec2::new(-4., 0.));
+    }
+
+    #[test]
+    fn food_doesnt_block() {
+        let grid = grid_with(Position { x: 40., y: 40. }, CollisionGroups::food());
+        assert_eq!(worker_at(28., 40., Vec2::new(4., 0.), &grid), Vec2::new(4., 0.));
+    }
+
+    #[test]
+    fn overlaps_dont_push_ants_oob() {
+        let mut world = World::default();
+        world.insert_resource(SpatialGrid::default());
+        let wall = world.spawn()
+            .insert_bundle((Position { x: 1.5, y: 40. }, Size::square(1.), CollisionGroups::wall()))
+            .id();
+        let ant = world.spawn()
+            .insert_bundle((Ant, Position { x: 0.5, y: 40.2 }, Size::square(1.), CollisionGroups::worker()))
+            .id();
+
+        let mut stage = SystemStage::single(resolve_overlaps);
+        stage.run(&mut world);
+
+        let pos = world.get::<Position>(ant).unwrap();
+        assert_eq!(pos.x, 0.5);
+        assert!(pos.y > 40.2);
+        assert_eq!(world.get::<Position>(wall).unwrap().x, 1.5);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::ant::{AntParams, ColonySpawner};
+use crate::food::FoodPolicy;
+use crate::genome::Genome;
+use crate::map::MapDef;
+use crate::plugins::AntFarmPlugins;
+use crate::scenario::Objective;
+use crate::script::ScriptSource;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// A file's contents, with its path in any error
+pub fn read_text(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// What every config, map and spec file goes through
+pub fn load_ron<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let source = read_text(path)?;
+    ron::from_str(&source).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub const DEFAULT_ANTS: usize = 30;
+// Simulated seconds per tick when not following the wall clock
+pub const DEFAULT_TICK_SECONDS: f32 = 1. / 30.;
+
+fn default_ants() -> usize {
+    DEFAULT_ANTS
+}
+
+fn default_tick_seconds() -> f32 {
+    DEFAULT_TICK_SECONDS
+}
+
+fn default_fog() -> bool {
+    true
+}
+
+// How to set up a run.  Saved next to a run's output, it's also what replays it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimConfig {
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // A name in assets/maps or a path to a map file
+    #[serde(default)]
+    pub map: Option<String>,
+    #[serde(default = "default_ants")]
+    pub ants: usize,
+    // Piles of food to start with, instead of the map's own
+    #[serde(default)]
+    pub food: Option<usize>,
+    // Replaces the map's food policies
+    #[serde(default)]
+    pub food_policy: Option<Vec<FoodPolicy>>,
+    #[serde(default)]
+    pub ticks: Option<u64>,
+    #[serde(default = "default_tick_seconds")]
+    pub tick_seconds: f32,
+    #[serde(default = "default_fog")]
+    pub fog: bool,
+    #[serde(default)]
+    pub params: AntParams,
+    // Overrides params.food_goal_hunger when given
+    #[serde(default)]
+    pub genome: Option<Genome>,
+    // A name in assets/scripts or a path to a .rhai file
+    #[serde(default)]
+    pub script: Option<String>,
+    // Decide the run's outcome when given, see scenario.rs
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            seed: None,
+            map: None,
+            ants: DEFAULT_ANTS,
+            food: None,
+            food_policy: None,
+            ticks: None,
+            tick_seconds: DEFAULT_TICK_SECONDS,
+            fog: true,
+            params: AntParams::default(),
+            genome: None,
+            script: None,
+            objectives: Vec::new(),
+        }
+    }
+}
+
+impl SimConfig {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_ron(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())?;
+        std::fs::write(path, source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn map_def(&self) -> Result<MapDef, String> {
+        let mut map = match &self.map {
+            Some(name) => MapDef::resolve(name)?,
+            None => MapDef::default(),
+        };
+        if let Some(policy) = &self.food_policy {
+            map.food = policy.clone();
+        }
+        Ok(match self.food {
+            Some(count) => map.with_initial_food(count),
+            None => map,
+        })
+    }
+
+    pub fn plugins(&self) -> Result<AntFarmPlugins, String> {
+        let mut plugins = AntFarmPlugins::default()
+            .map(self.map_def()?)
+            .spawner(ColonySpawner::workers(self.ants))
+            .fog(self.fog)
+            .params(self.params);
+        if let Some(genome) = self.genome {
+            plugins = plugins.genome(genome);
+        }
+        if let Some(script) = &self.script {
+            plugins = plugins.script(ScriptSource::resolve(script)?);
+        }
+        if !self.objectives.is_empty() {
+            plugins = plugins.objectives(self.objectives.clone());
+        }
+        Ok(plugins)
+    }
+
+    // Seeds the simulation rng, choosing a seed first if there isn't one so the run can be replayed
+    pub fn seed_rng(&mut self) -> u64 {
+        let seed = *self.seed.get_or_insert_with(rand::random);
+        crate::rng::seed(seed);
+        seed
+    }
+}
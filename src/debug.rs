@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use crate::arena::*;
+use crate::arena::Size;
+use crate::ant::*;
+use crate::collision::{SpatialGrid, GRID_CELL};
+use crate::gizmo::*;
+use crate::plugins::RequireExt;
+
+const VISION_KEY: KeyCode = KeyCode::F1;
+const COLLISION_KEY: KeyCode = KeyCode::F2;
+const DESTINATION_KEY: KeyCode = KeyCode::F3;
+const GRID_KEY: KeyCode = KeyCode::F4;
+
+const VISION_COLOR: Color = Color::rgba(0.3, 0.6, 1.0, 0.6);
+const COLLISION_COLOR: Color = Color::rgba(1.0, 0.3, 0.3, 0.8);
+const DESTINATION_COLOR: Color = Color::rgba(0.3, 1.0, 0.3, 0.6);
+const GRID_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.15);
+const GRID_FILL_COLOR: Color = Color::rgba(1.0, 1.0, 0.0, 0.05);
+
+// Pixels across the generated ring texture, and how much of the radius is ring
+const CIRCLE_TEXTURE_SIZE: u32 = 64;
+const CIRCLE_THICKNESS: f32 = 0.08;
+
+#[derive(Default)]
+pub struct DebugOverlays {
+    pub vision: bool,
+    pub collision: bool,
+    pub destinations: bool,
+    pub grid: bool,
+}
+
+struct CircleTexture(Handle<Image>);
+
+// Redrawn from scratch every frame
+#[derive(Component)]
+struct DebugGizmo;
+
+pub struct DebugOverlayPlugin;
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<ArenaStats>("DebugOverlayPlugin", "ArenaPlugin")
+            .requires::<SpatialGrid>("DebugOverlayPlugin", "CollisionPlugin")
+            .requires::<Input<KeyCode>>("DebugOverlayPlugin", "DefaultPlugins")
+            .requires::<Assets<Image>>("DebugOverlayPlugin", "DefaultPlugins")
+            .init_resource::<DebugOverlays>()
+            .add_startup_system(make_circle_texture)
+            .add_system(overlay_keys)
+            .add_system(draw_overlays.after(overlay_keys));
+    }
+}
+
+fn make_circle_texture(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let side = CIRCLE_TEXTURE_SIZE;
+    let half = side as f32 / 2.;
+    let mut data = Vec::with_capacity((side * side * 4) as usize);
+    for y in 0..side {
+        for x in 0..side {
+            let d = Vec2::new(x as f32 + 0.5 - half, y as f32 + 0.5 - half).length() / half;
+            let alpha = if d <= 1. && d >= 1. - CIRCLE_THICKNESS { 255 } else { 0 };
+            data.extend_from_slice(&[255, 255, 255, alpha]);
+        }
+    }
+
+    let image = Image::new(
+        Extent3d {
+            width: side,
+            height: side,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    commands.insert_resource(CircleTexture(images.add(image)));
+}
+
+fn overlay_keys(
+    keys: Res<Input<KeyCode>>,
+    mut overlays: ResMut<DebugOverlays>,
+) {
+    if keys.just_pressed(VISION_KEY) {
+        overlays.vision = !overlays.vision;
+    }
+    if keys.just_pressed(COLLISION_KEY) {
+        overlays.collision = !overlays.collision;
+    }
+    if keys.just_pressed(DESTINATION_KEY) {
+        overlays.destinations = !overlays.destinations;
+    }
+    if keys.just_pressed(GRID_KEY) {
+        overlays.grid = !overlays.grid;
+    }
+}
+
+fn circle(texture: &CircleTexture, centre: Vec2, radius: f32, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::splat(radius * 2.)),
+            ..Default::default()
+        },
+        texture: texture.0.clone(),
+        transform: Transform::from_translation(centre.extend(GIZMO_Z)),
+        ..Default::default()
+    }
+}
+
+fn line(from: Vec2, to: Vec2, thickness: f32, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            ..Default::default()
+        },
+        transform: line_transform(from, to, thickness),
+        ..Default::default()
+    }
+}
+
+fn draw_overlays(
+    mut commands: Commands,
+    overlays: Res<DebugOverlays>,
+    screen: Res<ArenaStats>,
+    grid: Res<SpatialGrid>,
+    texture: Option<Res<CircleTexture>>,
+    old: Query<Entity, With<DebugGizmo>>,
+    lookers: Query<(&Position, &VisibleRange)>,
+    colliders: Query<(&Position, &Size), With<CollisionGroups>>,
+    goals: Query<(&Position, &AntAI)>,
+) {
+    for e in old.iter() {
+        commands.entity(e).despawn();
+    }
+
+    let texture = match texture {
+        Some(t) => t,
+        None => return,
+    };
+    let mut gizmos: Vec<SpriteBundle> = Vec::new();
+
+    if overlays.vision {
+        for (p, v) in lookers.iter() {
+            gizmos.push(circle(&texture, screen.to_world(p), v.size.radius(), VISION_COLOR));
+        }
+    }
+
+    if overlays.collision {
+        for (p, s) in colliders.iter() {
+            gizmos.push(circle(&texture, screen.to_world(p), s.radius(), COLLISION_COLOR));
+        }
+    }
+
+    if overlays.destinations {
+        for (p, ai) in goals.iter() {
+            if let AiGoal::Destination { dest } = ai.ai {
+                gizmos.push(line(screen.to_world(p), screen.to_world(&dest), 1., DESTINATION_COLOR));
+            }
+        }
+    }
+
+    if overlays.grid {
+        let origin = screen.to_world(&Position { x: 0., y: 0. });
+        let extent = Vec2::new(grid.cols as f32, grid.rows as f32) * GRID_CELL;
+        for col in 0..=grid.cols {
+            let x = origin.x + col as f32 * GRID_CELL;
+            gizmos.push(line(Vec2::new(x, origin.y), Vec2::new(x, origin.y + extent.y), 1., GRID_COLOR));
+        }
+        for row in 0..=grid.rows {
+            let y = origin.y + row as f32 * GRID_CELL;
+            gizmos.push(line(Vec2::new(origin.x, y), Vec2::new(origin.x + extent.x, y), 1., GRID_COLOR));
+        }
+
+        // Busier cells are brighter
+        for row in 0..grid.rows {
+            for col in 0..grid.cols {
+                let count = grid.count(col, row);
+                if count == 0 {
+                    continue;
+                }
+                let centre = origin + (Vec2::new(col as f32, row as f32) + Vec2::splat(0.5)) * GRID_CELL;
+                let mut color = GRID_FILL_COLOR;
+                color.set_a((GRID_FILL_COLOR.a() * count as f32).min(0.5));
+                gizmos.push(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        ..Default::default()
+                    },
+                    transform: square_transform(centre, GRID_CELL),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    for bundle in gizmos.into_iter() {
+        commands.spawn_bundle(bundle).insert(DebugGizmo);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::config::{load_ron, SimConfig};
+use crate::genome::Genome;
+use crate::headless::{self, RunReport};
+use crate::sweep::run_parallel;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvolveSpec {
+    // Every genome runs on this, once per seed
+    pub base: SimConfig,
+    pub seeds: Vec<u64>,
+    pub population: usize,
+    pub generations: usize,
+    // Best genomes carried over unchanged each generation
+    pub elite: usize,
+    // Genomes compared to pick each parent
+    pub tournament: usize,
+    // Chance each gene mutates, and by up to what fraction of its range
+    pub mutation_rate: f32,
+    pub mutation_strength: f32,
+    // Fitness is survival_weight * fraction of the run survived
+    // plus food_weight * food eaten per starting ant
+    pub survival_weight: f32,
+    pub food_weight: f32,
+    // For the algorithm's own choices, the runs use seeds
+    pub rng_seed: u64,
+}
+
+impl Default for EvolveSpec {
+    fn default() -> Self {
+        EvolveSpec {
+            base: SimConfig::default(),
+            seeds: vec![1, 2, 3],
+            population: 20,
+            generations: 10,
+            elite: 2,
+            tournament: 3,
+            mutation_rate: 0.3,
+            mutation_strength: 0.1,
+            survival_weight: 1.0,
+            food_weight: 1.0,
+            rng_seed: 0,
+        }
+    }
+}
+
+impl EvolveSpec {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_ron(path)
+    }
+
+    fn fitness(&self, reports: &[RunReport]) -> f32 {
+        if reports.is_empty() {
+            return 0.;
+        }
+        let limit = self.base.ticks.unwrap_or(headless::DEFAULT_TICKS) as f64 * self.base.tick_seconds as f64;
+        let ants = self.base.ants.max(1) as f32;
+        let total: f32 = reports.iter()
+            .map(|r| {
+                let survival = (r.sim_seconds / limit).min(1.) as f32;
+                self.survival_weight * survival + self.food_weight * r.food_eaten / ants
+            })
+            .sum();
+        total / reports.len() as f32
+    }
+}
+
+impl EvolveSpec {
+    // Today's ants, hungering at the base config's rate
+    fn start(&self) -> Genome {
+        Genome { hunger_rate: self.base.params.hunger_rate, ..Genome::default() }
+    }
+
+    // Speed and hunger_rate cost nothing in a run, so selection would only push
+    // them to their limits.  Every genome keeps the starting ants' body and
+    // evolves how it steers, forages and sees.
+    fn keep_body(&self, genome: Genome) -> Genome {
+        let start = self.start();
+        Genome { speed: start.speed, hunger_rate: start.hunger_rate, ..genome }
+    }
+}
+
+// Evolves genomes over spec.generations, writing each generation's runs under
+// out/gen_N, the best genome so far to out/best.ron and progress to
+// out/generations.csv.  Returns the best genome and its fitness.
+pub fn evolve(spec: &EvolveSpec, exe: &Path, out: &Path, jobs: usize) -> Result<(Genome, f32), String> {
+    if spec.seeds.is_empty() {
+        return Err("Evolving needs at least one seed".to_string());
+    }
+    std::fs::create_dir_all(out).map_err(|e| format!("{}: {}", out.display(), e))?;
+    let log_path = out.join("generations.csv");
+    let mut log = std::fs::File::create(&log_path).map_err(|e| format!("{}: {}", log_path.display(), e))?;
+    writeln!(log, "generation,best,mean").map_err(|e| e.to_string())?;
+
+    // Start from today's ants and variations on them
+    let mut rng = StdRng::seed_from_u64(spec.rng_seed);
+    let size = spec.population.max(2);
+    let start = spec.start();
+    let mut population: Vec<Genome> = vec![start];
+    while population.len() < size {
+        population.push(spec.keep_body(start.mutate(&mut rng, 1., spec.mutation_strength * 3.)));
+    }
+
+    let mut best = (start, f32::MIN);
+    for generation in 0..spec.generations {
+        let scores = evaluate(spec, &population, exe, &out.join(format!("gen_{}", generation)), jobs);
+        let mut ranked: Vec<(Genome, f32)> = population.iter().copied().zip(scores.into_iter()).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mean = ranked.iter().map(|(_, f)| *f).sum::<f32>() / ranked.len() as f32;
+        if ranked[0].1 > best.1 {
+            best = ranked[0];
+            best.0.save(&out.join("best.ron"))?;
+        }
+        println!("Generation {}: best {:.3}, mean {:.3}", generation, ranked[0].1, mean);
+        writeln!(log, "{},{},{}", generation, ranked[0].1, mean).map_err(|e| e.to_string())?;
+
+        population = ranked.iter().take(spec.elite.min(size)).map(|(g, _)| *g).collect();
+        while population.len() < size {
+            let a = select(&ranked, spec.tournament, &mut rng);
+            let b = select(&ranked, spec.tournament, &mut rng);
+            let child = a.crossover(&b, &mut rng).mutate(&mut rng, spec.mutation_rate, spec.mutation_strength);
+            population.push(spec.keep_body(child));
+        }
+    }
+    Ok(best)
+}
+
+fn evaluate(spec: &EvolveSpec, population: &[Genome], exe: &Path, dir: &Path, jobs: usize) -> Vec<f32> {
+    let mut runs: Vec<(SimConfig, PathBuf)> = Vec::new();
+    for (i, genome) in population.iter().enumerate() {
+        for seed in spec.seeds.iter() {
+            let config = SimConfig {
+                seed: Some(*seed),
+                genome: Some(*genome),
+                ..spec.base.clone()
+            };
+            runs.push((config, dir.join(format!("genome_{}", i)).join(format!("seed_{}", seed))));
+        }
+    }
+
+    let results = run_parallel(exe, runs, jobs);
+    results.chunks(spec.seeds.len().max(1))
+        .enumerate()
+        .map(|(i, results)| {
+            let reports: Vec<RunReport> = results.iter()
+                .filter_map(|r| r.as_ref().ok().cloned())
+                .collect();
+            if reports.is_empty() {
+                println!("Every run of genome {} failed, scoring it 0", i);
+                for e in results.iter().filter_map(|r| r.as_ref().err()) {
+                    println!("  {}", e);
+                }
+            }
+            let fitness = spec.fitness(&reports);
+            // A NaN would otherwise rank above every real score
+            if fitness.is_finite() {
+                fitness
+            } else {
+                println!("Genome {} scored {}, counting it as 0", i, fitness);
+                0.
+            }
+        })
+        .collect()
+}
+
+// The fittest of a few picked at random
+fn select(ranked: &[(Genome, f32)], tournament: usize, rng: &mut impl Rng) -> Genome {
+    (0..tournament.max(1))
+        .map(|_| ranked[rng.gen_range(0, ranked.len())])
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offspring_keep_the_starting_body() {
+        let mut spec = EvolveSpec::default();
+        spec.base.params.hunger_rate = 0.03;
+        let start = spec.start();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let child = spec.keep_body(start.mutate(&mut rng, 1., 1.));
+            assert_eq!(child.speed, start.speed);
+            assert_eq!(child.hunger_rate, 0.03);
+        }
+    }
+}
@@ -8,19 +8,59 @@ use crate::ant::VisibleRange;
 enum FogPhase {
     Detect,
 }
+// Which tiles have had their fog cleared
+pub struct FogMap {
+    cleared: Vec<bool>,
+    cleared_count: usize,
+}
+
+impl Default for FogMap {
+    fn default() -> Self {
+        FogMap {
+            cleared: vec![false; (ARENA_WIDTH_TILES * ARENA_HEIGHT_TILES) as usize],
+            cleared_count: 0,
+        }
+    }
+}
+
+impl FogMap {
+    pub fn is_cleared(&self, col: u32, row: u32) -> bool {
+        self.cleared[(row * ARENA_WIDTH_TILES + col) as usize]
+    }
+
+    pub fn uncovered_fraction(&self) -> f32 {
+        self.cleared_count as f32 / self.cleared.len() as f32
+    }
+
+    fn clear(&mut self, col: u32, row: u32) {
+        let i = (row * ARENA_WIDTH_TILES + col) as usize;
+        if !self.cleared[i] {
+            self.cleared[i] = true;
+            self.cleared_count += 1;
+        }
+    }
+}
+
 pub struct FogOfWarPlugin;
 impl Plugin for FogOfWarPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<FogMap>()
             .add_startup_system(startup_spawn_fog)
             .add_system(find_visible.label(FogPhase::Detect))
             .add_system(fog_killer.after(FogPhase::Detect))
-            .add_event::<FogDieEvent>();
+            .add_event::<FogDieEvent>()
+            .add_event::<FogClearedEvent>();
     }
 }
 
 pub struct FogDieEvent {
-    fogs: Vec<Entity>,
+    pub fogs: Vec<Entity>,
+}
+
+// Tiles uncovered this update, as (col, row)
+pub struct FogClearedEvent {
+    pub tiles: Vec<(u32, u32)>,
 }
 
 #[derive(Component)]
@@ -72,11 +112,57 @@ fn find_visible(
 
 fn fog_killer(
     mut commands: Commands,
+    mut fog_map: ResMut<FogMap>,
     mut fog_death: EventReader<FogDieEvent>,
+    mut cleared_writer: EventWriter<FogClearedEvent>,
+    fog_pos: Query<&Position, With<Fog>>,
 ) {
+    let mut tiles = Vec::new();
     for event in fog_death.iter() {
         for fog_ent in event.fogs.iter() {
+            if let Some((col, row)) = fog_pos.get(*fog_ent).ok().and_then(tile_of) {
+                fog_map.clear(col, row);
+                tiles.push((col, row));
+            }
             commands.entity(*fog_ent).despawn();
         }
     }
-}
\ No newline at end of file
+    if !tiles.is_empty() {
+        cleared_writer.send(FogClearedEvent { tiles });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_fogged() {
+        let fog = FogMap::default();
+        assert_eq!(fog.uncovered_fraction(), 0.);
+        assert!(!fog.is_cleared(0, 0));
+    }
+
+    #[test]
+    fn clear_counts_each_tile_once() {
+        let mut fog = FogMap::default();
+        fog.clear(3, 4);
+        fog.clear(3, 4);
+        fog.clear(ARENA_WIDTH_TILES - 1, ARENA_HEIGHT_TILES - 1);
+        assert!(fog.is_cleared(3, 4));
+        assert!(!fog.is_cleared(4, 3));
+        let tiles = (ARENA_WIDTH_TILES * ARENA_HEIGHT_TILES) as f32;
+        assert_eq!(fog.uncovered_fraction(), 2. / tiles);
+    }
+
+    #[test]
+    fn fully_cleared() {
+        let mut fog = FogMap::default();
+        for row in 0..ARENA_HEIGHT_TILES {
+            for col in 0..ARENA_WIDTH_TILES {
+                fog.clear(col, row);
+            }
+        }
+        assert_eq!(fog.uncovered_fraction(), 1.);
+    }
+}
@@ -2,42 +2,286 @@ use bevy::prelude::*;
 use crate::arena::*;
 use crate::arena::Size;
 use crate::ant::KnownFood;
-use rand::prelude::random;
+use crate::clock::SimClock;
+use crate::map::MapDef;
+use crate::plugins::RequireExt;
+use crate::rng::{random, SimRng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Quantity a fresh pile of food spawns with, and the size it's drawn at then
+pub const FOOD_FULL: f32 = 3.;
+const FOOD_FULL_SIDE: f32 = 0.3;
+const FOOD_MIN_SIDE: f32 = 0.1;
 
 #[derive(Debug)]
 pub struct FoodCreateEvent {
     pub x: f32,
     pub y: f32,
     pub quantity: f32,
+    pub kind: FoodKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FoodKind {
+    Seed,
+    Sugar,
+    // Dead ants
+    Protein,
+}
+
+impl FoodKind {
+    // Hunger pct restored per unit eaten
+    pub fn nutrition(&self) -> f32 {
+        match self {
+            FoodKind::Seed => 1.0,
+            FoodKind::Sugar => 0.6,
+            FoodKind::Protein => 1.5,
+        }
+    }
+
+    // Units an ant gets through per second
+    pub fn eat_rate(&self) -> f32 {
+        match self {
+            FoodKind::Seed => 0.25,
+            FoodKind::Sugar => 0.5,
+            FoodKind::Protein => 0.15,
+        }
+    }
+
+    // Freshness lost per second, corpses go off long before seeds.  Rotting is
+    // the only way food spoils, it loses nutrition and then disappears.
+    pub fn rot_rate(&self) -> f32 {
+        match self {
+            FoodKind::Seed => 0.002,
+            FoodKind::Sugar => 0.005,
+            FoodKind::Protein => 0.025,
+        }
+    }
+
+    pub fn color(&self, known: bool) -> Color {
+        match (self, known) {
+            (FoodKind::Seed, false) => Color::PURPLE,
+            (FoodKind::Seed, true) => Color::ORANGE,
+            (FoodKind::Sugar, false) => Color::rgb(0.75, 0.75, 0.95),
+            (FoodKind::Sugar, true) => Color::rgb(1.0, 1.0, 0.6),
+            (FoodKind::Protein, false) => Color::rgb(0.5, 0.1, 0.1),
+            (FoodKind::Protein, true) => Color::rgb(0.95, 0.35, 0.3),
+        }
+    }
 }
 
 pub struct FoodPlugin;
 impl Plugin for FoodPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_startup_system(food_spawner)
+            .requires::<KnownFood>("FoodPlugin", "AntPlugin")
+            .requires::<SimClock>("FoodPlugin", "ClockPlugin")
+            .requires::<MapDef>("FoodPlugin", "MapPlugin")
+            .init_resource::<FoodSpawners>()
+            .add_startup_system(start_food_policies)
+            .add_system(run_food_policies)
             .add_system(food_coloration)
+            .add_system(food_sizing)
+            .add_system(food_rot)
             .add_system(food_create_handler)
             .add_event::<FoodCreateEvent>();
     }
 }
 
+// Below this freshness food is rotten, worth nothing, and ants leave it alone
+pub const ROTTEN: f32 = 0.3;
+const ROT_COLOR: Color = Color::rgb(0.3, 0.25, 0.1);
+
 #[derive(Component)]
 pub struct Food {
     pub quantity: f32,
+    pub kind: FoodKind,
+    // 1 when fresh, the food is gone at 0
+    pub freshness: f32,
+}
+
+impl Food {
+    pub fn new(quantity: f32, kind: FoodKind) -> Self {
+        Food {
+            quantity,
+            kind,
+            freshness: 1.,
+        }
+    }
+
+    // Spoils for dt seconds, true when it has just rotted away.  It's marked
+    // eaten too so nobody else despawns it.
+    pub fn rot(&mut self, dt: f32) -> bool {
+        if self.freshness <= 0. || self.quantity <= 0. {
+            return false;
+        }
+        self.freshness -= self.kind.rot_rate() * dt;
+        if self.freshness <= 0. {
+            self.quantity = 0.;
+            return true;
+        }
+        false
+    }
+
+    pub fn is_rotten(&self) -> bool {
+        self.freshness < ROTTEN
+    }
+
+    // Hunger pct restored per unit eaten right now
+    pub fn nutrition(&self) -> f32 {
+        if self.is_rotten() {
+            0.
+        } else {
+            self.kind.nutrition() * self.freshness
+        }
+    }
+}
+
+// How food turns up over a run, a map lists any number of these
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum FoodPolicy {
+    // Scatter count piles at the start
+    Initial { count: usize, kind: FoodKind },
+    // Every interval seconds drop count piles anywhere, while there are fewer than max piles
+    RandomDrops { interval: f32, count: usize, max: usize, kind: FoodKind },
+    // Keep up to capacity food within radius of a spot, growing a pile every interval seconds.
+    // Spot and radius are in tiles.
+    Patch { x: f32, y: f32, radius: f32, capacity: f32, interval: f32, kind: FoodKind },
+    // Pick sources spots at the start, then drop count piles within spread tiles of one of them
+    // every interval seconds, while there are fewer than max piles
+    Clustered { sources: usize, spread: f32, interval: f32, count: usize, max: usize, kind: FoodKind },
+    // Scales how quickly everything else spawns, from low to high and back over period seconds
+    Seasonal { period: f32, low: f32, high: f32 },
+}
+
+// Per policy countdowns and cluster centres, in the same order as the map's policies
+#[derive(Default)]
+pub struct FoodSpawners {
+    countdowns: Vec<f32>,
+    sources: Vec<Vec<Vec2>>,
+}
+
+// Somewhere at least two tiles in from the walls
+fn random_spot() -> Vec2 {
+    let x: f32 = random::<f32>() * ((ARENA_WIDTH_TILES - 4) as f32 * ARENA_TILE_SIDE) + (2. * ARENA_TILE_SIDE);
+    let y: f32 = random::<f32>() * ((ARENA_HEIGHT_TILES - 4) as f32 * ARENA_TILE_SIDE) + (2. * ARENA_TILE_SIDE);
+    Vec2::new(x, y)
 }
 
-pub fn food_spawner(
+fn spot_near(centre: Vec2, radius: f32) -> Vec2 {
+    let mut rng = SimRng;
+    let angle = rng.gen_range(0., std::f32::consts::TAU);
+    // sqrt keeps the spread even across the disc
+    let dist = radius * rng.gen_range(0f32, 1.).sqrt();
+    let spot = centre + Vec2::new(angle.cos(), angle.sin()) * dist;
+    spot.clamp(
+        Vec2::splat(2. * ARENA_TILE_SIDE),
+        Vec2::new((ARENA_WIDTH_TILES - 3) as f32, (ARENA_HEIGHT_TILES - 3) as f32) * ARENA_TILE_SIDE,
+    )
+}
+
+fn spawn_food(commands: &mut Commands, at: Vec2, quantity: f32, kind: FoodKind) {
+    commands.spawn_bundle(FoodBundle::new(at.x, at.y, quantity, kind));
+}
+
+// Product of every seasonal policy at this moment
+pub fn abundance(policies: &[FoodPolicy], now: f64) -> f32 {
+    policies.iter()
+        .map(|policy| match policy {
+            FoodPolicy::Seasonal { period, low, high } if *period > 0. => {
+                let phase = (now / *period as f64 * std::f64::consts::TAU).sin() as f32;
+                low + (high - low) * (0.5 + 0.5 * phase)
+            },
+            _ => 1.,
+        })
+        .product()
+}
+
+pub fn start_food_policies(
     mut commands: Commands,
-    food_count: Query<&Food>,
+    map: Res<MapDef>,
+    mut spawners: ResMut<FoodSpawners>,
 ) {
-    let mut current_food = food_count.iter().count();
-    while current_food < 40 {
-        let x: f32 = random::<f32>() * ((ARENA_WIDTH_TILES - 4) as f32 * ARENA_TILE_SIDE) + (2. * ARENA_TILE_SIDE);
-        let y: f32 = random::<f32>() * ((ARENA_HEIGHT_TILES - 4) as f32 * ARENA_TILE_SIDE) + (2. * ARENA_TILE_SIDE);
+    spawners.countdowns = Vec::new();
+    spawners.sources = Vec::new();
+    for policy in map.food.iter() {
+        let mut sources = Vec::new();
+        match policy {
+            FoodPolicy::Initial { count, kind } => {
+                for _ in 0..*count {
+                    spawn_food(&mut commands, random_spot(), FOOD_FULL, *kind);
+                }
+            },
+            FoodPolicy::Clustered { sources: n, .. } => {
+                sources = (0..*n).map(|_| random_spot()).collect();
+            },
+            _ => {},
+        }
+        spawners.countdowns.push(0.);
+        spawners.sources.push(sources);
+    }
+}
 
-        commands.spawn_bundle(FoodBundle::new(x, y, 3.));
-        current_food += 1;
+pub fn run_food_policies(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    map: Res<MapDef>,
+    mut spawners: ResMut<FoodSpawners>,
+    food: Query<(&Food, &Position)>,
+) {
+    // Seasons make everything come round faster or slower
+    let dt = clock.delta_seconds() * abundance(&map.food, clock.elapsed_seconds());
+    if dt <= 0. || spawners.countdowns.len() != map.food.len() {
+        return;
+    }
+    let piles = food.iter().count();
+
+    for (i, policy) in map.food.iter().enumerate() {
+        let interval = match policy {
+            FoodPolicy::RandomDrops { interval, .. }
+            | FoodPolicy::Patch { interval, .. }
+            | FoodPolicy::Clustered { interval, .. } => *interval,
+            FoodPolicy::Initial { .. } | FoodPolicy::Seasonal { .. } => continue,
+        };
+        spawners.countdowns[i] -= dt;
+        if spawners.countdowns[i] > 0. {
+            continue;
+        }
+        spawners.countdowns[i] += interval.max(0.1);
+
+        match policy {
+            FoodPolicy::RandomDrops { count, max, kind, .. } => {
+                for _ in 0..(*count).min(max.saturating_sub(piles)) {
+                    spawn_food(&mut commands, random_spot(), FOOD_FULL, *kind);
+                }
+            },
+            FoodPolicy::Patch { x, y, radius, capacity, kind, .. } => {
+                let centre = Vec2::new(*x, *y) * ARENA_TILE_SIDE;
+                let reach = *radius * ARENA_TILE_SIDE;
+                // Rotten piles are only waiting to go, the patch grows back around them
+                let held: f32 = food.iter()
+                    .filter(|(f, _)| f.kind == *kind && !f.is_rotten())
+                    .filter(|(_, p)| centre.distance(Vec2::new(p.x, p.y)) <= reach)
+                    .map(|(f, _)| f.quantity.max(0.))
+                    .sum();
+                let room = (capacity - held).min(FOOD_FULL);
+                if room > 0. {
+                    spawn_food(&mut commands, spot_near(centre, reach), room, *kind);
+                }
+            },
+            FoodPolicy::Clustered { spread, count, max, kind, .. } => {
+                let sources = &spawners.sources[i];
+                if sources.is_empty() {
+                    continue;
+                }
+                let source = sources[SimRng.gen_range(0, sources.len())];
+                for _ in 0..(*count).min(max.saturating_sub(piles)) {
+                    spawn_food(&mut commands, spot_near(source, *spread * ARENA_TILE_SIDE), FOOD_FULL, *kind);
+                }
+            },
+            FoodPolicy::Initial { .. } | FoodPolicy::Seasonal { .. } => {},
+        }
     }
 }
 
@@ -51,21 +295,55 @@ pub fn food_create_handler(
     }
 }
 
-
-const KNOWN_FOOD_COLOR: Color = Color::ORANGE;
+// Known food stands out, and everything browns as it rots
 fn food_coloration(
     known_food: Res<KnownFood>,
-    mut food_sprites: Query<&mut Sprite, With<Food>>,
+    mut food_sprites: Query<(Entity, &Food, Option<&mut Sprite>, Option<&mut TextureAtlasSprite>)>,
 ) {
-    for food_ent in known_food.locs.iter() {
-        if let Ok(mut sprite) = food_sprites.get_mut(*food_ent) {
-            sprite.color = KNOWN_FOOD_COLOR;
+    for (e, food, opt_sprite, opt_sheet) in food_sprites.iter_mut() {
+        let fresh = food.kind.color(known_food.locs.contains(&e));
+        let rot = 1. - food.freshness.clamp(0., 1.);
+        let color = Color::rgb(
+            fresh.r() + (ROT_COLOR.r() - fresh.r()) * rot,
+            fresh.g() + (ROT_COLOR.g() - fresh.g()) * rot,
+            fresh.b() + (ROT_COLOR.b() - fresh.b()) * rot,
+        );
+        if let Some(mut sprite) = opt_sprite {
+            sprite.color = color;
+        }
+        if let Some(mut sheet) = opt_sheet {
+            sheet.color = color;
+        }
+    }
+}
+
+// Piles shrink as they're eaten
+fn food_sizing(
+    mut food: Query<(&Food, &mut Size), Changed<Food>>,
+) {
+    for (f, mut size) in food.iter_mut() {
+        let full = (f.quantity / FOOD_FULL).clamp(0., 1.);
+        *size = Size::square(FOOD_MIN_SIDE + (FOOD_FULL_SIDE - FOOD_MIN_SIDE) * full);
+    }
+}
+
+// Fully spoiled food goes away by itself, and clean_food forgets it.
+// Whoever takes food to zero despawns it, so eat_food and this never both do.
+fn food_rot(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    mut food: Query<(Entity, &mut Food)>,
+) {
+    let dt = clock.delta_seconds();
+    for (e, mut f) in food.iter_mut() {
+        if f.rot(dt) {
+            commands.entity(e).despawn();
         }
     }
 }
 
 #[derive(Bundle)]
-struct FoodBundle {
+pub struct FoodBundle {
     #[bundle]
     sprite: SpriteBundle,
 
@@ -73,6 +351,7 @@ struct FoodBundle {
     position: Position,
     layer: Layer,
     size: Size,
+    collision: CollisionGroups,
 }
 
 impl Default for FoodBundle {
@@ -85,30 +364,77 @@ impl Default for FoodBundle {
                 },
                 ..Default::default()
             },
-            food: Food {quantity: 3.},
+            food: Food::new(FOOD_FULL, FoodKind::Seed),
             position: Position { x: 500., y: 500.},
             layer: Layer::Main1,
-            size: crate::arena::Size::square(0.3)
+            size: crate::arena::Size::square(FOOD_FULL_SIDE),
+            collision: CollisionGroups::food(),
         }
     }
 }
 
 impl FoodBundle {
-    fn new(x: f32, y: f32, quantity: f32) -> FoodBundle {
-        FoodBundle {
+    pub fn new(x: f32, y: f32, quantity: f32, kind: FoodKind) -> FoodBundle {
+        let mut bundle = FoodBundle {
             position: Position {x, y},
-            food: Food {quantity},
+            food: Food::new(quantity, kind),
             ..FoodBundle::default()
-        }
+        };
+        bundle.sprite.sprite.color = kind.color(false);
+        bundle
     }
 }
 
 impl From<&FoodCreateEvent> for FoodBundle {
     fn from(event: &FoodCreateEvent) -> Self {
-        FoodBundle {
-            position: Position {x: event.x, y: event.y},
-            food: Food {quantity: event.quantity},
-            ..FoodBundle::default()
-        }
+        FoodBundle::new(event.x, event.y, event.quantity, event.kind)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nutrition_falls_with_freshness() {
+        let mut food = Food::new(FOOD_FULL, FoodKind::Seed);
+        let fresh = food.nutrition();
+        assert_eq!(fresh, FoodKind::Seed.nutrition());
+        food.freshness = 0.5;
+        assert!(food.nutrition() < fresh);
+        assert!(food.nutrition() > 0.);
+    }
+
+    #[test]
+    fn rotten_food_is_worthless() {
+        let mut food = Food::new(FOOD_FULL, FoodKind::Protein);
+        food.freshness = ROTTEN - 0.01;
+        assert!(food.is_rotten());
+        assert_eq!(food.nutrition(), 0.);
+    }
+
+    #[test]
+    fn protein_rots_before_seed() {
+        let mut seed = Food::new(FOOD_FULL, FoodKind::Seed);
+        let mut protein = Food::new(FOOD_FULL, FoodKind::Protein);
+        seed.rot(10.);
+        protein.rot(10.);
+        assert!(protein.freshness < seed.freshness);
+    }
+
+    #[test]
+    fn rots_away_once() {
+        let mut food = Food::new(FOOD_FULL, FoodKind::Protein);
+        let lifetime = 1. / FoodKind::Protein.rot_rate();
+        assert!(!food.rot(lifetime * 0.9));
+        assert!(food.rot(lifetime * 0.2));
+        assert_eq!(food.quantity, 0.);
+        assert!(!food.rot(1.));
+    }
+
+    #[test]
+    fn eaten_food_doesnt_rot() {
+        let mut food = Food::new(0., FoodKind::Sugar);
+        assert!(!food.rot(1e6));
+        assert_eq!(food.freshness, 1.);
+    }
+}
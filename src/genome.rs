@@ -0,0 +1,361 @@
+use bevy::prelude::*;
+use crate::ant::*;
+use crate::behaviour::BehaviourLibrary;
+use crate::arena::*;
+use crate::clock::SimClock;
+use crate::config::load_ron;
+use crate::plugins::RequireExt;
+use crate::rng::SimRng;
+use crate::steering::SteeringWeights;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+pub const GENE_COUNT: usize = 9;
+pub const GENE_NAMES: [&str; GENE_COUNT] = [
+    "wander",
+    "seek",
+    "avoid_walls",
+    "separate",
+    "wander_duration",
+    "food_goal_hunger",
+    "vision",
+    "speed",
+    "hunger_rate",
+];
+// Lowest and highest value of each gene, in the order of Genome::genes
+const GENE_RANGES: [(f32, f32); GENE_COUNT] = [
+    (0., 5.),
+    (0., 5.),
+    (0., 8.),
+    (0., 5.),
+    (0.5, 20.),
+    (0.05, 0.9),
+    (1., 15.),
+    (20., 100.),
+    (0.01, 0.05),
+];
+
+// The queen lays while she's at least this well fed, and it costs her
+const LAY_HUNGER: f32 = 0.5;
+const LAY_COST: f32 = 0.15;
+// Simulated seconds between eggs
+const LAY_INTERVAL: f32 = 30.;
+// How much offspring differ from their parents, see Genome::mutate
+const INHERIT_MUTATION_RATE: f32 = 0.2;
+const INHERIT_MUTATION_STRENGTH: f32 = 0.05;
+
+// How a worker moves, forages and sees.  As a resource it's what the colony
+// starts with.  Every ant also carries its own, which offspring inherit, and
+// workers go by theirs.  The queen only passes hers on, she lives by AntParams.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Genome {
+    // SteeringWeights
+    pub wander: f32,
+    pub seek: f32,
+    pub avoid_walls: f32,
+    pub separate: f32,
+    // Seconds a worker keeps wandering one way
+    pub wander_duration: f32,
+    pub food_goal_hunger: f32,
+    pub vision: f32,
+    // Arena units per second
+    pub speed: f32,
+    // Hunger pct lost per second
+    pub hunger_rate: f32,
+}
+
+impl Default for Genome {
+    // The ants as they are without one
+    fn default() -> Self {
+        let weights = SteeringWeights::default();
+        Genome {
+            wander: weights.wander,
+            seek: weights.seek,
+            avoid_walls: weights.avoid_walls,
+            separate: weights.separate,
+            wander_duration: BehaviourLibrary::default().worker.wander_duration,
+            food_goal_hunger: FOOD_GOAL_HUNGER,
+            vision: ANT_VISION,
+            speed: ANT_SPEED,
+            hunger_rate: HUNGER_DEGRADATION_RATE,
+        }
+    }
+}
+
+impl Genome {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_ron(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())?;
+        std::fs::write(path, source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn genes(&self) -> [f32; GENE_COUNT] {
+        [
+            self.wander,
+            self.seek,
+            self.avoid_walls,
+            self.separate,
+            self.wander_duration,
+            self.food_goal_hunger,
+            self.vision,
+            self.speed,
+            self.hunger_rate,
+        ]
+    }
+
+    pub fn from_genes(genes: [f32; GENE_COUNT]) -> Self {
+        let g: Vec<f32> = genes.iter()
+            .zip(GENE_RANGES.iter())
+            .map(|(v, (lo, hi))| v.clamp(*lo, *hi))
+            .collect();
+        Genome {
+            wander: g[0],
+            seek: g[1],
+            avoid_walls: g[2],
+            separate: g[3],
+            wander_duration: g[4],
+            food_goal_hunger: g[5],
+            vision: g[6],
+            speed: g[7],
+            hunger_rate: g[8],
+        }
+    }
+
+    pub fn steering(&self) -> SteeringWeights {
+        SteeringWeights {
+            wander: self.wander,
+            seek: self.seek,
+            avoid_walls: self.avoid_walls,
+            separate: self.separate,
+        }
+    }
+
+    // Each gene has a rate chance of moving by up to strength of its range
+    pub fn mutate(&self, rng: &mut impl Rng, rate: f32, strength: f32) -> Self {
+        let mut genes = self.genes();
+        for (gene, (lo, hi)) in genes.iter_mut().zip(GENE_RANGES.iter()) {
+            if rng.gen::<f32>() < rate {
+                *gene += rng.gen_range(-1f32, 1f32) * strength * (hi - lo);
+            }
+        }
+        Genome::from_genes(genes)
+    }
+
+    // Each gene taken from one parent or the other
+    pub fn crossover(&self, other: &Genome, rng: &mut impl Rng) -> Self {
+        let mut genes = self.genes();
+        for (gene, theirs) in genes.iter_mut().zip(other.genes().iter()) {
+            if rng.gen::<bool>() {
+                *gene = *theirs;
+            }
+        }
+        Genome::from_genes(genes)
+    }
+}
+
+// Generations counted from the starting colony at 0
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Generation(pub u32);
+
+// Every genome born into each generation, for seeing how traits drift
+#[derive(Default)]
+pub struct GeneticsLog {
+    pub generations: BTreeMap<u32, Vec<Genome>>,
+}
+
+impl GeneticsLog {
+    pub fn record(&mut self, generation: Generation, genome: Genome) {
+        self.generations.entry(generation.0).or_default().push(genome);
+    }
+
+    // One row per generation and gene
+    pub fn write_csv(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        writeln!(file, "generation,gene,count,mean,std,min,max")?;
+        for (generation, genomes) in self.generations.iter() {
+            let n = genomes.len() as f32;
+            for (g, name) in GENE_NAMES.iter().enumerate() {
+                let values: Vec<f32> = genomes.iter().map(|genome| genome.genes()[g]).collect();
+                let mean = values.iter().sum::<f32>() / n;
+                let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n).sqrt();
+                let min = values.iter().copied().fold(f32::MAX, f32::min);
+                let max = values.iter().copied().fold(f32::MIN, f32::max);
+                writeln!(file, "{},{},{},{},{},{},{}", generation, name, values.len(), mean, std, min, max)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct LayTimer(Timer);
+
+pub struct GenomePlugin;
+impl Plugin for GenomePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<SteeringWeights>("GenomePlugin", "AntPlugin")
+            .requires::<AntParams>("GenomePlugin", "AntPlugin")
+            .requires::<BehaviourLibrary>("GenomePlugin", "BehaviourPlugin")
+            .requires::<SimClock>("GenomePlugin", "ClockPlugin")
+            .init_resource::<GeneticsLog>()
+            .insert_resource(LayTimer(Timer::from_seconds(LAY_INTERVAL, true)))
+            .add_system_to_stage(CoreStage::PreUpdate, apply_genome)
+            .add_system(sync_vision
+                .before(BigPhase::Decide)
+            )
+            .add_system(lay_eggs
+                .label(BigPhase::Act)
+                .after(BigPhase::Move)
+            );
+    }
+}
+
+// The colony's genome sets the defaults, and goes to every ant without its own
+fn apply_genome(
+    mut commands: Commands,
+    genome: Option<Res<Genome>>,
+    mut weights: ResMut<SteeringWeights>,
+    mut params: ResMut<AntParams>,
+    mut library: ResMut<BehaviourLibrary>,
+    mut log: ResMut<GeneticsLog>,
+    unborn: Query<Entity, (With<Ant>, Without<Genome>)>,
+) {
+    if let Some(genome) = &genome {
+        if genome.is_changed() {
+            *weights = genome.steering();
+            params.food_goal_hunger = genome.food_goal_hunger;
+            library.worker.wander_duration = genome.wander_duration;
+        }
+    }
+
+    // Without one, the founders are today's ants at the colony's hunger rate
+    let founder = genome.map(|g| *g).unwrap_or_else(|| Genome {
+        hunger_rate: params.hunger_rate,
+        food_goal_hunger: params.food_goal_hunger,
+        ..Genome::default()
+    });
+    for ant in unborn.iter() {
+        commands.entity(ant)
+            .insert(founder)
+            .insert(Generation(0));
+        log.record(Generation(0), founder);
+    }
+}
+
+fn sync_vision(
+    mut workers: Query<(&Genome, &mut VisibleRange), (Changed<Genome>, Without<Queen>)>,
+) {
+    for (genome, mut range) in workers.iter_mut() {
+        *range = VisibleRange::new(genome.vision);
+    }
+}
+
+// A well fed queen lays a worker every LAY_INTERVAL.  She mates with a worker
+// picked at random, so the ants that live longest father the most.
+fn lay_eggs(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    mut timer: ResMut<LayTimer>,
+    mut log: ResMut<GeneticsLog>,
+    mut queens: Query<(&Position, &mut Hunger, &Genome, &Generation), With<Queen>>,
+    workers: Query<(&Genome, &Generation), (With<Ant>, Without<Queen>)>,
+) {
+    if !timer.0.tick(clock.delta()).just_finished() {
+        return;
+    }
+
+    let mates: Vec<(&Genome, &Generation)> = workers.iter().collect();
+    let mut rng = SimRng;
+    for (pos, mut hunger, genome, generation) in queens.iter_mut() {
+        if hunger.pct < LAY_HUNGER {
+            continue;
+        }
+        hunger.pct -= LAY_COST;
+
+        let (child, generation) = match mates.get(rng.gen_range(0, mates.len().max(1))) {
+            Some((mate, mate_generation)) => (
+                genome.crossover(mate, &mut rng),
+                generation.0.max(mate_generation.0) + 1,
+            ),
+            None => (*genome, generation.0 + 1),
+        };
+        let child = child.mutate(&mut rng, INHERIT_MUTATION_RATE, INHERIT_MUTATION_STRENGTH);
+        commands
+            .spawn_bundle(AntBundle::new(pos.x, pos.y))
+            .insert(child)
+            .insert(Generation(generation));
+        log.record(Generation(generation), child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn in_range(genome: &Genome) -> bool {
+        genome.genes().iter()
+            .zip(GENE_RANGES.iter())
+            .all(|(v, (lo, hi))| v >= lo && v <= hi)
+    }
+
+    #[test]
+    fn from_genes_clamps() {
+        let low = Genome::from_genes([-100.; GENE_COUNT]);
+        let high = Genome::from_genes([100.; GENE_COUNT]);
+        for (g, (lo, hi)) in GENE_RANGES.iter().enumerate() {
+            assert_eq!(low.genes()[g], *lo, "gene {}", g);
+            assert_eq!(high.genes()[g], *hi, "gene {}", g);
+        }
+    }
+
+    #[test]
+    fn genes_round_trip() {
+        let genome = Genome::default();
+        assert!(in_range(&genome));
+        assert_eq!(Genome::from_genes(genome.genes()), genome);
+    }
+
+    #[test]
+    fn mutate_stays_in_range() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut genome = Genome::default();
+        for _ in 0..200 {
+            genome = genome.mutate(&mut rng, 1., 1.);
+            assert!(in_range(&genome), "{:?}", genome);
+        }
+    }
+
+    #[test]
+    fn mutate_without_rate_changes_nothing() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let genome = Genome::default();
+        assert_eq!(genome.mutate(&mut rng, 0., 1.), genome);
+    }
+
+    #[test]
+    fn crossover_takes_each_gene_from_a_parent() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let a = Genome::from_genes([f32::MIN; GENE_COUNT]);
+        let b = Genome::from_genes([f32::MAX; GENE_COUNT]);
+        for _ in 0..20 {
+            let child = a.crossover(&b, &mut rng);
+            for (g, gene) in child.genes().iter().enumerate() {
+                assert!(*gene == a.genes()[g] || *gene == b.genes()[g], "{}", GENE_NAMES[g]);
+            }
+        }
+    }
+}
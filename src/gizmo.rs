@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+
+// Drawn over everything else, fog included
+pub const GIZMO_Z: f32 = 2.0;
+
+// Stretch a unit sprite into a line between two world points
+pub fn line_transform(from: Vec2, to: Vec2, thickness: f32) -> Transform {
+    let delta = to - from;
+    Transform {
+        translation: ((from + to) / 2.).extend(GIZMO_Z),
+        rotation: Quat::from_rotation_z(delta.y.atan2(delta.x)),
+        scale: Vec3::new(delta.length(), thickness, 1.),
+    }
+}
+
+// A unit sprite covering a square of side `side` around centre
+pub fn square_transform(centre: Vec2, side: f32) -> Transform {
+    Transform {
+        translation: centre.extend(GIZMO_Z),
+        scale: Vec3::new(side, side, 1.),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,367 @@
+use bevy::prelude::*;
+use crate::arena::*;
+use crate::ant::*;
+use crate::config::SimConfig;
+use crate::food::Food;
+use crate::headless;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+// Tiles either side of the agent in its observation grid
+pub const VIEW_RADIUS: i32 = 5;
+pub const ACTIONS: usize = 8;
+// Simulation updates per step
+const FRAMES_PER_STEP: u32 = 4;
+const MAX_STEPS: u32 = 5000;
+// Per unit of food eaten, per step alive and for dying
+const FOOD_REWARD: f32 = 1.0;
+const ALIVE_REWARD: f32 = 0.001;
+const DEATH_REWARD: f32 = -1.0;
+// The agent starts eating food in reach when hungrier than this
+const EAT_BELOW: f32 = 0.9;
+const EAT_REACH: f32 = ARENA_TILE_SIDE;
+
+// Observation grid cells, the most important thing on a tile wins
+pub const CELL_EMPTY: u8 = 0;
+pub const CELL_ANT: u8 = 1;
+pub const CELL_FOOD: u8 = 2;
+pub const CELL_WALL: u8 = 3;
+
+// Action n heads this way, the same directions a wandering ant picks from
+const DIRECTIONS: [AiGoal; ACTIONS] = [
+    AiGoal::North,
+    AiGoal::NE,
+    AiGoal::East,
+    AiGoal::SE,
+    AiGoal::South,
+    AiGoal::SW,
+    AiGoal::West,
+    AiGoal::NW,
+];
+
+// The worker moved by the trainer, which the behaviour system leaves alone
+#[derive(Component)]
+pub struct Agent;
+
+// Food the agent ate since the last step
+#[derive(Default)]
+struct AgentFood(f32);
+
+// One JSON object per line, such as {"cmd": "step", "action": 3}
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum GymRequest {
+    Spec,
+    Reset {
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Step {
+        action: usize,
+    },
+    Close,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FoodSeen {
+    // Arena units from the agent
+    pub dx: f32,
+    pub dy: f32,
+    pub quantity: f32,
+    pub rotten: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Observation {
+    // Rows top first, the agent in the middle, see the CELL_ constants
+    pub grid: Vec<Vec<u8>>,
+    pub food: Vec<FoodSeen>,
+    pub hunger: f32,
+    pub health: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepInfo {
+    pub steps: u32,
+    pub eaten: f32,
+    pub colony: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum GymResponse {
+    Spec {
+        actions: usize,
+        view_radius: i32,
+        frames_per_step: u32,
+        max_steps: u32,
+    },
+    Reset {
+        observation: Observation,
+    },
+    Step {
+        observation: Observation,
+        reward: f32,
+        done: bool,
+        info: StepInfo,
+    },
+    Closed {
+        closed: bool,
+    },
+    Error {
+        error: String,
+    },
+}
+
+// One headless colony with one ant under outside control
+pub struct GymEnv {
+    config: SimConfig,
+    app: Option<App>,
+    agent: Option<Entity>,
+    steps: u32,
+    done: bool,
+}
+
+impl GymEnv {
+    pub fn new(config: SimConfig) -> Self {
+        GymEnv {
+            config,
+            app: None,
+            agent: None,
+            steps: 0,
+            done: true,
+        }
+    }
+
+    pub fn handle(&mut self, request: GymRequest) -> GymResponse {
+        let result = match request {
+            GymRequest::Spec => Ok(GymResponse::Spec {
+                actions: ACTIONS,
+                view_radius: VIEW_RADIUS,
+                frames_per_step: FRAMES_PER_STEP,
+                max_steps: MAX_STEPS,
+            }),
+            GymRequest::Reset { seed } => self.reset(seed).map(|observation| GymResponse::Reset { observation }),
+            GymRequest::Step { action } => self.step(action).map(|(observation, reward, done, info)| {
+                GymResponse::Step { observation, reward, done, info }
+            }),
+            GymRequest::Close => {
+                *self = GymEnv::new(self.config.clone());
+                Ok(GymResponse::Closed { closed: true })
+            },
+        };
+        result.unwrap_or_else(|error| GymResponse::Error { error })
+    }
+
+    // A fresh colony, with the agent one of its workers
+    pub fn reset(&mut self, seed: Option<u64>) -> Result<Observation, String> {
+        let mut config = self.config.clone();
+        if seed.is_some() {
+            config.seed = seed;
+        }
+        config.seed_rng();
+
+        let mut app = headless::headless_app(&config)?;
+        app
+            .init_resource::<AgentFood>()
+            .add_system(record_agent_food.after(BigPhase::Move));
+        // Startup spawns the colony
+        app.update();
+
+        let agent = app.world
+            .query_filtered::<Entity, (With<Ant>, Without<Queen>)>()
+            .iter(&app.world)
+            .min()
+            .ok_or("The colony has no workers to control")?;
+        app.world.entity_mut(agent).insert(Agent);
+
+        let observation = observe(&mut app.world, agent);
+        self.app = Some(app);
+        self.agent = Some(agent);
+        self.steps = 0;
+        self.done = false;
+        Ok(observation)
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<(Observation, f32, bool, StepInfo), String> {
+        let (app, agent) = match (self.app.as_mut(), self.agent) {
+            (Some(app), Some(agent)) if !self.done => (app, agent),
+            _ => return Err("Reset before stepping".to_string()),
+        };
+        let goal = *DIRECTIONS.get(action).ok_or_else(|| format!("Actions are 0 to {}", ACTIONS - 1))?;
+
+        // Eating carries on whatever the action
+        if app.world.get::<AntEating>(agent).is_none() {
+            let reach = food_in_reach(&mut app.world, agent);
+            let hungry = app.world.get::<Hunger>(agent).map_or(false, |h| h.pct < EAT_BELOW);
+            let mut ant = app.world.entity_mut(agent);
+            match reach {
+                Some(food_ent) if hungry => {
+                    ant.insert(AntEating { food_ent });
+                    ant.insert(AntAI { ai: AiGoal::Wait, duration: 0. });
+                },
+                _ => {
+                    ant.insert(AntAI { ai: goal, duration: f32::MAX });
+                },
+            }
+        }
+
+        app.world.get_resource_mut::<AgentFood>().unwrap().0 = 0.;
+        for _ in 0..FRAMES_PER_STEP {
+            app.update();
+            if app.world.get_entity(agent).is_none() {
+                break;
+            }
+        }
+        self.steps += 1;
+
+        let alive = app.world.get_entity(agent).is_some();
+        let eaten = app.world.get_resource::<AgentFood>().unwrap().0;
+        let reward = FOOD_REWARD * eaten + if alive { ALIVE_REWARD } else { DEATH_REWARD };
+        self.done = !alive || self.steps >= MAX_STEPS;
+
+        let info = StepInfo {
+            steps: self.steps,
+            eaten,
+            colony: app.world.query_filtered::<Entity, With<Ant>>().iter(&app.world).count(),
+        };
+        Ok((observe(&mut app.world, agent), reward, self.done, info))
+    }
+}
+
+fn record_agent_food(
+    mut food: ResMut<AgentFood>,
+    mut eaten: EventReader<FoodEatenEvent>,
+    agents: Query<Entity, With<Agent>>,
+) {
+    for event in eaten.iter() {
+        if agents.get(event.ant).is_ok() {
+            food.0 += event.amount;
+        }
+    }
+}
+
+fn food_in_reach(world: &mut World, agent: Entity) -> Option<Entity> {
+    let pos = *world.get::<Position>(agent)?;
+    world.query::<(Entity, &Position, &Food)>()
+        .iter(world)
+        .filter(|(_, _, f)| f.quantity > 0.)
+        .find(|(_, p, _)| Vec2::new(p.x - pos.x, p.y - pos.y).length() < EAT_REACH)
+        .map(|(e, _, _)| e)
+}
+
+// A dead agent sees nothing
+fn observe(world: &mut World, agent: Entity) -> Observation {
+    let side = (2 * VIEW_RADIUS + 1) as usize;
+    let pos = match world.get::<Position>(agent) {
+        Some(p) => *p,
+        None => {
+            return Observation {
+                grid: vec![vec![CELL_EMPTY; side]; side],
+                food: Vec::new(),
+                hunger: 0.,
+                health: 0.,
+            }
+        },
+    };
+    let tile = |p: &Position| ((p.x / ARENA_TILE_SIDE).round() as i32, (p.y / ARENA_TILE_SIDE).round() as i32);
+    let (centre_col, centre_row) = tile(&pos);
+
+    // Off the arena counts as wall
+    let mut grid = vec![vec![CELL_EMPTY; side]; side];
+    for (r, line) in grid.iter_mut().enumerate() {
+        for (c, cell) in line.iter_mut().enumerate() {
+            let col = centre_col + c as i32 - VIEW_RADIUS;
+            let row = centre_row + VIEW_RADIUS - r as i32;
+            if col < 0 || row < 0 || col >= ARENA_WIDTH_TILES as i32 || row >= ARENA_HEIGHT_TILES as i32 {
+                *cell = CELL_WALL;
+            }
+        }
+    }
+    let mut mark = |p: &Position, value: u8| {
+        let (col, row) = tile(p);
+        let (dc, dr) = (col - centre_col, row - centre_row);
+        if dc.abs() <= VIEW_RADIUS && dr.abs() <= VIEW_RADIUS {
+            let cell = &mut grid[(VIEW_RADIUS - dr) as usize][(dc + VIEW_RADIUS) as usize];
+            *cell = (*cell).max(value);
+        }
+    };
+
+    for (p, groups) in world.query::<(&Position, &CollisionGroups)>().iter(world) {
+        if groups.memberships & CollisionGroups::WALL != 0 {
+            mark(p, CELL_WALL);
+        }
+    }
+    for (e, p) in world.query_filtered::<(Entity, &Position), With<Ant>>().iter(world) {
+        if e != agent {
+            mark(p, CELL_ANT);
+        }
+    }
+    let mut food = Vec::new();
+    let reach = VIEW_RADIUS as f32 * ARENA_TILE_SIDE;
+    for (p, f) in world.query::<(&Position, &Food)>().iter(world) {
+        mark(p, CELL_FOOD);
+        let (dx, dy) = (p.x - pos.x, p.y - pos.y);
+        if dx.abs() <= reach && dy.abs() <= reach {
+            food.push(FoodSeen {
+                dx,
+                dy,
+                quantity: f.quantity,
+                rotten: f.is_rotten(),
+            });
+        }
+    }
+
+    Observation {
+        grid,
+        food,
+        hunger: world.get::<Hunger>(agent).map_or(0., |h| h.pct),
+        health: world.get::<Health>(agent).map_or(0., |h| h.pct),
+    }
+}
+
+// Serves one trainer at a time on 127.0.0.1:port, one JSON request and
+// response per line.  A close request or disconnect frees it for the next.
+pub fn serve(config: SimConfig, port: u16) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+    println!("Gym listening on 127.0.0.1:{}", port);
+
+    let mut env = GymEnv::new(config.clone());
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_client(&mut env, stream) {
+                    println!("Trainer disconnected: {}", e);
+                }
+                // The next trainer starts afresh, even if this one left without closing
+                env = GymEnv::new(config.clone());
+            },
+            Err(e) => println!("Couldn't accept a trainer: {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn handle_client(env: &mut GymEnv, stream: TcpStream) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (response, close) = match serde_json::from_str::<GymRequest>(&line) {
+            Ok(request) => {
+                let close = matches!(request, GymRequest::Close);
+                (env.handle(request), close)
+            },
+            Err(e) => (GymResponse::Error { error: e.to_string() }, false),
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+        if close {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy::core::DefaultTaskPoolOptions;
+use crate::ant::Ant;
+use crate::clock::SimClock;
+use crate::config::{load_ron, SimConfig};
+use crate::genome::GeneticsLog;
+use crate::recruit::RecruitStats;
+use crate::scenario::{ObjectiveStatus, ObjectiveTracker, TrackedObjective};
+use crate::stats::ColonyStats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+// Ten simulated minutes at the default tick
+pub const DEFAULT_TICKS: u64 = 18_000;
+// How long a paused run waits between updates
+const PAUSED_SLEEP_MS: u64 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    // Still had ants when the tick limit came
+    Survived,
+    ColonyDied,
+    // Runs with objectives end as soon as they're decided
+    ObjectivesMet,
+    ObjectivesFailed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunReport {
+    pub outcome: Outcome,
+    pub ticks: u64,
+    pub sim_seconds: f64,
+    pub population: usize,
+    pub peak_population: usize,
+    pub deaths: u32,
+    pub births: u32,
+    pub food_in_world: f32,
+    pub food_eaten: f32,
+    pub ant_seconds: f64,
+    pub fog_uncovered: f32,
+    #[serde(default)]
+    pub objectives: Vec<TrackedObjective>,
+    #[serde(default)]
+    pub recruitment: RecruitStats,
+}
+
+impl RunReport {
+    // Ant lifetime bought with each unit of food eaten
+    pub fn food_efficiency(&self) -> f64 {
+        if self.food_eaten > 0. {
+            self.ant_seconds / self.food_eaten as f64
+        } else {
+            0.
+        }
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Outcome: {:?} after {} ticks ({:.0}s simulated)", self.outcome, self.ticks, self.sim_seconds)?;
+        writeln!(f, "Population: {} (peak {})", self.population, self.peak_population)?;
+        writeln!(f, "Deaths: {}, births: {}", self.deaths, self.births)?;
+        writeln!(f, "Food eaten: {:.1}, {:.0} ant seconds per food", self.food_eaten, self.food_efficiency())?;
+        write!(f, "Food in world: {:.1}, fog uncovered: {:.1}%", self.food_in_world, self.fog_uncovered * 100.)?;
+        if self.recruitment.any() {
+            write!(f, "\nRecruitment: {}", self.recruitment)?;
+        }
+        for tracked in self.objectives.iter() {
+            write!(f, "\n{}: {:?}", tracked.objective, tracked.status)?;
+        }
+        Ok(())
+    }
+}
+
+// The simulation without a window, ticking config.tick_seconds per update.
+// Systems run on one thread so a seeded run plays out the same way again.
+pub fn headless_app(config: &SimConfig) -> Result<App, String> {
+    let mut app = App::new();
+    app
+        .insert_resource(DefaultTaskPoolOptions::with_num_threads(1))
+        .insert_resource(SimClock::fixed(config.tick_seconds))
+        .add_plugins(MinimalPlugins)
+        .add_plugins(config.plugins()?.headless(true));
+    Ok(app)
+}
+
+fn colony_alive(world: &mut World) -> bool {
+    world.query_filtered::<Entity, With<Ant>>().iter(world).next().is_some()
+}
+
+// Update until max_ticks, until every ant is dead or until the objectives are decided
+// Paused updates, such as while under remote control, don't count as ticks
+pub fn run(app: &mut App, max_ticks: u64) -> RunReport {
+    let mut ticks = 0;
+    loop {
+        app.update();
+        let paused = app.world.get_resource::<SimClock>().map_or(false, |c| c.delta_seconds() == 0.);
+        if paused {
+            std::thread::sleep(std::time::Duration::from_millis(PAUSED_SLEEP_MS));
+            continue;
+        }
+        ticks += 1;
+        let decided = app.world.get_resource::<ObjectiveTracker>().map_or(false, |t| t.status() != ObjectiveStatus::Pending);
+        if decided || ticks >= max_ticks || !colony_alive(&mut app.world) {
+            break;
+        }
+    }
+    finish(&mut app.world, ticks)
+}
+
+// The report for a run that has stopped, however it stopped
+pub fn finish(world: &mut World, ticks: u64) -> RunReport {
+    let alive = colony_alive(world);
+    // With objectives, they're what the run was for
+    let outcome = match world.get_resource_mut::<ObjectiveTracker>() {
+        Some(mut tracker) => {
+            tracker.finish();
+            match tracker.status() {
+                ObjectiveStatus::Met => Outcome::ObjectivesMet,
+                _ => Outcome::ObjectivesFailed,
+            }
+        },
+        None if alive => Outcome::Survived,
+        None => Outcome::ColonyDied,
+    };
+    report(world, ticks, outcome)
+}
+
+pub fn report(world: &World, ticks: u64, outcome: Outcome) -> RunReport {
+    let stats = world.get_resource::<ColonyStats>();
+    let clock = world.get_resource::<SimClock>();
+    RunReport {
+        outcome,
+        ticks,
+        sim_seconds: clock.map_or(0., |c| c.elapsed_seconds()),
+        population: stats.map_or(0, |s| s.population),
+        peak_population: stats.map_or(0, |s| s.peak_population),
+        deaths: stats.map_or(0, |s| s.deaths),
+        births: stats.map_or(0, |s| s.births),
+        food_in_world: stats.map_or(0., |s| s.food_in_world),
+        food_eaten: stats.map_or(0., |s| s.food_eaten),
+        ant_seconds: stats.map_or(0., |s| s.ant_seconds),
+        fog_uncovered: stats.map_or(0., |s| s.fog_uncovered),
+        objectives: world.get_resource::<ObjectiveTracker>().map_or(Vec::new(), |t| t.objectives.clone()),
+        recruitment: world.get_resource::<RecruitStats>().copied().unwrap_or_default(),
+    }
+}
+
+// run.ron to replay the run, report.ron, the stats history as stats.csv
+// and trait distributions per generation as genetics.csv
+pub fn save_run(world: &World, config: &SimConfig, report: &RunReport, out: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(out).map_err(|e| format!("{}: {}", out.display(), e))?;
+    config.save(&out.join("run.ron"))?;
+
+    let path = out.join("report.ron");
+    let source = ron::ser::to_string_pretty(report, ron::ser::PrettyConfig::new())
+        .map_err(|e| e.to_string())?;
+    std::fs::write(&path, source).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    if let Some(stats) = world.get_resource::<ColonyStats>() {
+        let path = out.join("stats.csv");
+        stats.write_csv(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    if let Some(log) = world.get_resource::<GeneticsLog>() {
+        let path = out.join("genetics.csv");
+        log.write_csv(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+pub fn load_report(path: &Path) -> Result<RunReport, String> {
+    load_ron(path)
+}
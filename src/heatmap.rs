@@ -0,0 +1,374 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use crate::arena::*;
+use crate::ant::*;
+use crate::clock::SimClock;
+use crate::plugins::RequireExt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const LAYER_KEY: KeyCode = KeyCode::H;
+const EXPORT_KEY: KeyCode = KeyCode::P;
+const EXPORT_DIR: &str = "heatmaps";
+
+// Between the fog and the gizmos
+const HEATMAP_Z: f32 = 1.5;
+// Real seconds between overlay texture redraws
+const REDRAW_INTERVAL: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatLayer {
+    // Seconds ants spent on each tile
+    Visits,
+    Deaths,
+    // Food quantity eaten on each tile
+    Eaten,
+}
+
+impl HeatLayer {
+    pub const ALL: [HeatLayer; 3] = [HeatLayer::Visits, HeatLayer::Deaths, HeatLayer::Eaten];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HeatLayer::Visits => "visits",
+            HeatLayer::Deaths => "deaths",
+            HeatLayer::Eaten => "eaten",
+        }
+    }
+}
+
+// Per tile totals, row 0 is the bottom of the arena
+pub struct Heatmap {
+    visits: Vec<f32>,
+    deaths: Vec<f32>,
+    eaten: Vec<f32>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        let tiles = (ARENA_WIDTH_TILES * ARENA_HEIGHT_TILES) as usize;
+        Heatmap {
+            visits: vec![0.; tiles],
+            deaths: vec![0.; tiles],
+            eaten: vec![0.; tiles],
+        }
+    }
+}
+
+impl Heatmap {
+    pub fn layer(&self, layer: HeatLayer) -> &[f32] {
+        match layer {
+            HeatLayer::Visits => &self.visits,
+            HeatLayer::Deaths => &self.deaths,
+            HeatLayer::Eaten => &self.eaten,
+        }
+    }
+
+    pub fn get(&self, layer: HeatLayer, col: u32, row: u32) -> f32 {
+        self.layer(layer)[(row * ARENA_WIDTH_TILES + col) as usize]
+    }
+
+    fn add(&mut self, layer: HeatLayer, pos: &Position, amount: f32) {
+        if let Some((col, row)) = tile_of(pos) {
+            let i = (row * ARENA_WIDTH_TILES + col) as usize;
+            match layer {
+                HeatLayer::Visits => self.visits[i] += amount,
+                HeatLayer::Deaths => self.deaths[i] += amount,
+                HeatLayer::Eaten => self.eaten[i] += amount,
+            }
+        }
+    }
+
+    // One line per arena row, top row first so it reads like the screen
+    pub fn write_csv(&self, layer: HeatLayer, path: &Path) -> std::io::Result<()> {
+        let mut file = fs::File::create(path)?;
+        for row in (0..ARENA_HEIGHT_TILES).rev() {
+            let line: Vec<String> = (0..ARENA_WIDTH_TILES)
+                .map(|col| format!("{}", self.get(layer, col, row)))
+                .collect();
+            writeln!(file, "{}", line.join(","))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_png(&self, layer: HeatLayer, path: &Path) -> image::ImageResult<()> {
+        image::save_buffer(
+            path,
+            &self.rgba(layer),
+            ARENA_WIDTH_TILES,
+            ARENA_HEIGHT_TILES,
+            image::ColorType::Rgba8,
+        )
+    }
+
+    // Scaled against the hottest tile, top row first
+    fn rgba(&self, layer: HeatLayer) -> Vec<u8> {
+        let values = self.layer(layer);
+        let max = values.iter().copied().fold(0., f32::max);
+        let mut data = Vec::with_capacity(values.len() * 4);
+        for row in (0..ARENA_HEIGHT_TILES).rev() {
+            for col in 0..ARENA_WIDTH_TILES {
+                let heat = if max > 0. { (self.get(layer, col, row) / max).sqrt() } else { 0. };
+                data.extend_from_slice(&heat_color(heat));
+            }
+        }
+        data
+    }
+}
+
+// Black through red and yellow to white, transparent when cold
+fn heat_color(heat: f32) -> [u8; 4] {
+    let channel = |x: f32| (x.clamp(0., 1.) * 255.) as u8;
+    [
+        channel(heat * 3.),
+        channel(heat * 3. - 1.),
+        channel(heat * 3. - 2.),
+        channel(heat * 2.),
+    ]
+}
+
+pub struct HeatmapSettings {
+    pub shown: Option<HeatLayer>,
+    // Exports go in timestamped directories under here
+    pub export_dir: PathBuf,
+}
+
+impl Default for HeatmapSettings {
+    fn default() -> Self {
+        HeatmapSettings {
+            shown: None,
+            export_dir: PathBuf::from(EXPORT_DIR),
+        }
+    }
+}
+
+struct HeatmapOverlay {
+    texture: Handle<Image>,
+    redraw: Timer,
+}
+
+#[derive(Component)]
+struct HeatmapSprite;
+
+pub struct HeatmapPlugin;
+impl Plugin for HeatmapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<Events<AntDeathEvent>>("HeatmapPlugin", "AntPlugin")
+            .requires::<Events<FoodEatenEvent>>("HeatmapPlugin", "AntPlugin")
+            .requires::<ArenaStats>("HeatmapPlugin", "ArenaPlugin")
+            .requires::<SimClock>("HeatmapPlugin", "ClockPlugin")
+            .requires::<Input<KeyCode>>("HeatmapPlugin", "DefaultPlugins")
+            .requires::<Assets<Image>>("HeatmapPlugin", "DefaultPlugins")
+            .init_resource::<Heatmap>()
+            .init_resource::<HeatmapSettings>()
+            .add_startup_system(spawn_overlay.after(StartupLabels::Screen))
+            .add_system(record_visits
+                .after(BigPhase::Move)
+            )
+            .add_system(record_deaths
+                .after(BigPhase::Ambient)
+                .before(BigPhase::Cleanup)
+            )
+            .add_system(record_eaten
+                .after(BigPhase::Move)
+            )
+            .add_system(heatmap_keys)
+            .add_system(update_overlay.after(heatmap_keys));
+    }
+}
+
+fn record_visits(
+    clock: Res<SimClock>,
+    mut heatmap: ResMut<Heatmap>,
+    ants: Query<&Position, With<Ant>>,
+) {
+    let dt = clock.delta_seconds();
+    if dt <= 0. {
+        return;
+    }
+    for p in ants.iter() {
+        heatmap.add(HeatLayer::Visits, p, dt);
+    }
+}
+
+fn record_deaths(
+    mut heatmap: ResMut<Heatmap>,
+    mut deaths: EventReader<AntDeathEvent>,
+    locations: Query<&Position, With<Ant>>,
+) {
+    for death in deaths.iter() {
+        if let Ok(p) = locations.get(death.ent) {
+            heatmap.add(HeatLayer::Deaths, p, 1.);
+        }
+    }
+}
+
+fn record_eaten(
+    mut heatmap: ResMut<Heatmap>,
+    mut eaten: EventReader<FoodEatenEvent>,
+) {
+    for event in eaten.iter() {
+        heatmap.add(HeatLayer::Eaten, &event.at, event.amount);
+    }
+}
+
+fn spawn_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    screen: Res<ArenaStats>,
+) {
+    let image = Image::new(
+        Extent3d {
+            width: ARENA_WIDTH_TILES,
+            height: ARENA_HEIGHT_TILES,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        vec![0; (ARENA_WIDTH_TILES * ARENA_HEIGHT_TILES * 4) as usize],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    let texture = images.add(image);
+
+    // Tiles are centred on multiples of the tile side, so the map is offset by half a tile
+    let centre = screen.to_world(&Position {
+        x: (ARENA_WIDTH_TILES - 1) as f32 * ARENA_TILE_SIDE / 2.,
+        y: (ARENA_HEIGHT_TILES - 1) as f32 * ARENA_TILE_SIDE / 2.,
+    });
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(
+                    ARENA_WIDTH_TILES as f32 * ARENA_TILE_SIDE,
+                    ARENA_HEIGHT_TILES as f32 * ARENA_TILE_SIDE,
+                )),
+                ..Default::default()
+            },
+            texture: texture.clone(),
+            transform: Transform::from_translation(centre.extend(HEATMAP_Z)),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(HeatmapSprite);
+
+    commands.insert_resource(HeatmapOverlay {
+        texture,
+        redraw: Timer::from_seconds(REDRAW_INTERVAL, true),
+    });
+}
+
+fn heatmap_keys(
+    keys: Res<Input<KeyCode>>,
+    heatmap: Res<Heatmap>,
+    mut settings: ResMut<HeatmapSettings>,
+) {
+    if keys.just_pressed(LAYER_KEY) {
+        settings.shown = match settings.shown {
+            None => Some(HeatLayer::Visits),
+            Some(HeatLayer::Visits) => Some(HeatLayer::Deaths),
+            Some(HeatLayer::Deaths) => Some(HeatLayer::Eaten),
+            Some(HeatLayer::Eaten) => None,
+        };
+    }
+    if keys.just_pressed(EXPORT_KEY) {
+        match export_heatmap(&heatmap, &settings.export_dir) {
+            Ok(dir) => println!("Heatmaps written to {}", dir.display()),
+            Err(e) => println!("Couldn't export heatmaps: {}", e),
+        }
+    }
+}
+
+// Every layer as both CSV and PNG into one timestamped directory
+pub fn export_heatmap(heatmap: &Heatmap, root: &Path) -> Result<PathBuf, String> {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let dir = root.join(format!("{}", stamp));
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    for layer in HeatLayer::ALL {
+        heatmap.write_csv(layer, &dir.join(format!("{}.csv", layer.name())))
+            .map_err(|e| e.to_string())?;
+        heatmap.write_png(layer, &dir.join(format!("{}.png", layer.name())))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(dir)
+}
+
+fn update_overlay(
+    time: Res<Time>,
+    heatmap: Res<Heatmap>,
+    settings: Res<HeatmapSettings>,
+    overlay: Option<ResMut<HeatmapOverlay>>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprites: Query<&mut Visibility, With<HeatmapSprite>>,
+) {
+    let mut overlay = match overlay {
+        Some(o) => o,
+        None => return,
+    };
+    for mut visibility in sprites.iter_mut() {
+        if visibility.is_visible != settings.shown.is_some() {
+            visibility.is_visible = settings.shown.is_some();
+        }
+    }
+
+    let layer = match settings.shown {
+        Some(layer) => layer,
+        None => return,
+    };
+    // Redraw straight away when switching layers
+    let switched = settings.is_changed();
+    if !overlay.redraw.tick(time.delta()).just_finished() && !switched {
+        return;
+    }
+    if let Some(image) = images.get_mut(&overlay.texture) {
+        image.data = heatmap.rgba(layer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_goes_to_the_tile() {
+        let mut heatmap = Heatmap::default();
+        let pos = Position { x: 3. * ARENA_TILE_SIDE, y: 2. * ARENA_TILE_SIDE };
+        heatmap.add(HeatLayer::Visits, &pos, 0.5);
+        heatmap.add(HeatLayer::Visits, &pos, 0.25);
+        assert_eq!(heatmap.get(HeatLayer::Visits, 3, 2), 0.75);
+        assert_eq!(heatmap.get(HeatLayer::Deaths, 3, 2), 0.);
+        assert_eq!(heatmap.layer(HeatLayer::Visits).iter().sum::<f32>(), 0.75);
+    }
+
+    #[test]
+    fn add_outside_the_arena_is_dropped() {
+        let mut heatmap = Heatmap::default();
+        heatmap.add(HeatLayer::Deaths, &Position { x: -100., y: 0. }, 1.);
+        assert_eq!(heatmap.layer(HeatLayer::Deaths).iter().sum::<f32>(), 0.);
+    }
+
+    #[test]
+    fn csv_has_the_top_row_first() {
+        let mut heatmap = Heatmap::default();
+        let top = (ARENA_HEIGHT_TILES - 1) as f32 * ARENA_TILE_SIDE;
+        heatmap.add(HeatLayer::Eaten, &Position { x: 0., y: top }, 2.);
+        heatmap.add(HeatLayer::Eaten, &Position { x: ARENA_TILE_SIDE, y: 0. }, 1.);
+
+        let path = std::env::temp_dir().join(format!("antfarm_heatmap_{}.csv", std::process::id()));
+        heatmap.write_csv(HeatLayer::Eaten, &path).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let rows: Vec<Vec<f32>> = csv.lines()
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), ARENA_HEIGHT_TILES as usize);
+        assert!(rows.iter().all(|r| r.len() == ARENA_WIDTH_TILES as usize));
+        assert_eq!(rows[0][0], 2.);
+        assert_eq!(rows[ARENA_HEIGHT_TILES as usize - 1][1], 1.);
+    }
+}
@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+use crate::camera::UiFont;
+use crate::clock::SimClock;
+use crate::recruit::RecruitStats;
+use crate::scenario::{ObjectiveStatus, ObjectiveTracker};
+use crate::stats::{ColonyStats, StatsSample, HISTORY_MINUTES};
+use crate::plugins::RequireExt;
+
+const GRAPH_KEY: KeyCode = KeyCode::G;
+const PAUSE_KEY: KeyCode = KeyCode::Space;
+const FASTER_KEY: KeyCode = KeyCode::Equals;
+const SLOWER_KEY: KeyCode = KeyCode::Minus;
+
+// Each sparkline is this many bars, each bar averaging a slice of the history
+const SPARK_BARS: usize = 60;
+const SPARK_BAR_WIDTH: f32 = 3.;
+const SPARK_HEIGHT: f32 = 40.;
+const POPULATION_COLOR: Color = Color::rgb(0.7, 0.7, 0.7);
+const FOOD_COLOR: Color = Color::ORANGE;
+
+#[derive(Default)]
+pub struct HudSettings {
+    pub graphs: bool,
+}
+
+#[derive(Component)]
+struct HudText;
+
+#[derive(Component)]
+struct Sparklines;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum SparkKind {
+    Population,
+    Food,
+}
+
+// One bar of a sparkline, index 0 is the oldest
+#[derive(Component)]
+struct SparkBar {
+    kind: SparkKind,
+    index: usize,
+}
+
+pub struct HudPlugin;
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<ColonyStats>("HudPlugin", "StatsPlugin")
+            .requires::<SimClock>("HudPlugin", "ClockPlugin")
+            .requires::<Input<KeyCode>>("HudPlugin", "DefaultPlugins")
+            .init_resource::<HudSettings>()
+            .add_startup_system(spawn_sparklines)
+            .add_system(hud_keys)
+            .add_system(update_hud_text)
+            .add_system(update_sparklines);
+    }
+}
+
+fn hud_keys(
+    keys: Res<Input<KeyCode>>,
+    mut clock: ResMut<SimClock>,
+    mut settings: ResMut<HudSettings>,
+) {
+    if keys.just_pressed(PAUSE_KEY) {
+        clock.paused = !clock.paused;
+    }
+    if keys.just_pressed(FASTER_KEY) {
+        let speed = clock.speed * 2.;
+        clock.set_speed(speed);
+    }
+    if keys.just_pressed(SLOWER_KEY) {
+        let speed = clock.speed / 2.;
+        clock.set_speed(speed);
+    }
+    if keys.just_pressed(GRAPH_KEY) {
+        settings.graphs = !settings.graphs;
+    }
+}
+
+fn spawn_hud_text(commands: &mut Commands, font: &UiFont) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.),
+                    right: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 16.,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(HudText);
+}
+
+fn update_hud_text(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    stats: Res<ColonyStats>,
+    tracker: Option<Res<ObjectiveTracker>>,
+    recruitment: Option<Res<RecruitStats>>,
+    font: Option<Res<UiFont>>,
+    mut texts: Query<&mut Text, With<HudText>>,
+) {
+    // The font is loaded alongside the camera
+    let mut text = match texts.iter_mut().next() {
+        Some(t) => t,
+        None => {
+            if let Some(font) = font {
+                spawn_hud_text(&mut commands, &font);
+            }
+            return;
+        },
+    };
+
+    let elapsed = clock.elapsed_seconds() as u64;
+    let speed = if clock.paused {
+        "paused".to_string()
+    } else {
+        format!("x{}", clock.speed)
+    };
+
+    let mut lines = vec![
+        format!("Population: {}", stats.population),
+        format!("Deaths: {}", stats.deaths),
+        format!("Births: {}", stats.births),
+        format!("Food in world: {:.1}", stats.food_in_world),
+        format!("Average hunger: {:.0}%", stats.average_hunger * 100.),
+        format!("Fog uncovered: {:.1}%", stats.fog_uncovered * 100.),
+        format!("Time: {}:{:02} ({})", elapsed / 60, elapsed % 60, speed),
+    ];
+    if let Some(recruitment) = recruitment.filter(|r| r.any()) {
+        lines.push(format!("Recruitment: {}", *recruitment));
+    }
+    if let Some(tracker) = tracker {
+        lines.push(match tracker.status() {
+            ObjectiveStatus::Pending => "Objectives:".to_string(),
+            ObjectiveStatus::Met => "Objectives: won!".to_string(),
+            ObjectiveStatus::Failed => "Objectives: lost".to_string(),
+        });
+        for tracked in tracker.objectives.iter() {
+            let progress = match tracked.status {
+                ObjectiveStatus::Pending => tracked.objective.progress(&stats, clock.elapsed_seconds()),
+                ObjectiveStatus::Met => "done".to_string(),
+                ObjectiveStatus::Failed => "failed".to_string(),
+            };
+            lines.push(format!("  {} ({})", tracked.objective, progress));
+        }
+    }
+    text.sections[0].value = lines.join("\n");
+}
+
+fn spawn_sparklines(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(10.),
+                    left: Val::Px(10.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::Column,
+                display: Display::None,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0., 0., 0., 0.5)),
+            ..Default::default()
+        })
+        .insert(Sparklines)
+        .with_children(|graphs| {
+            for kind in [SparkKind::Population, SparkKind::Food] {
+                spawn_sparkline(graphs, kind);
+            }
+        });
+}
+
+fn spawn_sparkline(parent: &mut ChildBuilder, kind: SparkKind) {
+    let color = match kind {
+        SparkKind::Population => POPULATION_COLOR,
+        SparkKind::Food => FOOD_COLOR,
+    };
+
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: bevy::math::Size::new(Val::Px(SPARK_BARS as f32 * SPARK_BAR_WIDTH), Val::Px(SPARK_HEIGHT)),
+                margin: Rect::all(Val::Px(4.)),
+                flex_direction: FlexDirection::Row,
+                // Bars grow up from the bottom of the graph
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            color: UiColor(Color::NONE),
+            ..Default::default()
+        })
+        .with_children(|line| {
+            for index in 0..SPARK_BARS {
+                line
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: bevy::math::Size::new(Val::Px(SPARK_BAR_WIDTH), Val::Px(0.)),
+                            ..Default::default()
+                        },
+                        color: UiColor(color),
+                        ..Default::default()
+                    })
+                    .insert(SparkBar { kind, index });
+            }
+        });
+}
+
+// Average the history into SPARK_BARS buckets, newest on the right
+fn bucket(history: &[StatsSample], value: impl Fn(&StatsSample) -> f32) -> Vec<f32> {
+    let per_bar = (HISTORY_MINUTES * 60 / SPARK_BARS).max(1);
+    let mut bars = vec![0.; SPARK_BARS];
+    for (i, chunk) in history.rchunks(per_bar).take(SPARK_BARS).enumerate() {
+        let total: f32 = chunk.iter().map(|s| value(s)).sum();
+        bars[SPARK_BARS - 1 - i] = total / chunk.len() as f32;
+    }
+    bars
+}
+
+fn update_sparklines(
+    settings: Res<HudSettings>,
+    stats: Res<ColonyStats>,
+    mut graphs: Query<&mut Style, (With<Sparklines>, Without<SparkBar>)>,
+    mut bars: Query<(&SparkBar, &mut Style), Without<Sparklines>>,
+) {
+    let display = if settings.graphs { Display::Flex } else { Display::None };
+    for mut style in graphs.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    if !settings.graphs {
+        return;
+    }
+
+    let history: Vec<StatsSample> = stats.history.iter().copied().collect();
+    let population = bucket(&history, |s| s.population as f32);
+    let food = bucket(&history, |s| s.food);
+    let scale = |values: &[f32]| {
+        let max = values.iter().copied().fold(0., f32::max);
+        if max > 0. { SPARK_HEIGHT / max } else { 0. }
+    };
+    let (population_scale, food_scale) = (scale(&population), scale(&food));
+
+    for (bar, mut style) in bars.iter_mut() {
+        let height = match bar.kind {
+            SparkKind::Population => population[bar.index] * population_scale,
+            SparkKind::Food => food[bar.index] * food_scale,
+        };
+        style.size.height = Val::Px(height);
+    }
+}
@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use crate::arena::*;
+use crate::arena::Size;
+use crate::ant::*;
+use crate::behaviour::CurrentAction;
+use crate::camera::{cursor_to_world, MainCamera, Selected, UiFont};
+use crate::food::Food;
+use crate::genome::{Generation, Genome};
+use crate::gizmo::*;
+use crate::plugins::RequireExt;
+
+const SELECT_BUTTON: MouseButton = MouseButton::Left;
+// Small things are hard to click, give them at least this much room
+const MIN_PICK_RADIUS: f32 = ARENA_TILE_SIDE;
+const HIGHLIGHT_COLOR: Color = Color::rgba(1.0, 0.9, 0.2, 0.5);
+const DESTINATION_COLOR: Color = Color::rgba(1.0, 0.9, 0.2, 0.8);
+// Goals set to last 100000s (scripted ones, say) end when something changes them
+const ENDLESS_GOAL_SECONDS: f32 = 10000.;
+
+#[derive(Component)]
+struct SelectionHighlight;
+
+#[derive(Component)]
+struct DestinationLine;
+
+#[derive(Component)]
+struct InspectorText;
+
+pub struct InspectorPlugin;
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<ArenaStats>("InspectorPlugin", "ArenaPlugin")
+            .requires::<Selected>("InspectorPlugin", "CameraPlugin")
+            .requires::<Windows>("InspectorPlugin", "DefaultPlugins")
+            .add_startup_system(spawn_inspector)
+            .add_system(click_select)
+            .add_system(update_inspector_text.after(click_select))
+            .add_system(update_highlight.after(click_select));
+    }
+}
+
+fn spawn_inspector(mut commands: Commands) {
+    commands
+        .spawn_bundle(hidden_sprite(HIGHLIGHT_COLOR))
+        .insert(SelectionHighlight);
+    commands
+        .spawn_bundle(hidden_sprite(DESTINATION_COLOR))
+        .insert(DestinationLine);
+}
+
+fn hidden_sprite(color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            ..Default::default()
+        },
+        visibility: Visibility { is_visible: false },
+        ..Default::default()
+    }
+}
+
+fn click_select(
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    screen: Res<ArenaStats>,
+    mut selected: ResMut<Selected>,
+    cameras: Query<&Transform, With<MainCamera>>,
+    pickable: Query<(Entity, &Position, &Size), Or<(With<Ant>, With<Food>)>>,
+    ui: Query<&Interaction>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        selected.0 = None;
+    }
+    if !buttons.just_pressed(SELECT_BUTTON) {
+        return;
+    }
+    // Clicks on the minimap and other UI aren't for the world underneath
+    if ui.iter().any(|i| *i != Interaction::None) {
+        return;
+    }
+
+    let world = match (windows.get_primary(), cameras.iter().next()) {
+        (Some(window), Some(camera)) => cursor_to_world(window, camera),
+        _ => None,
+    };
+    let clicked = match world {
+        Some(w) => screen.to_arena(w),
+        None => return,
+    };
+    let at = Vec2::from((clicked.x, clicked.y));
+
+    selected.0 = pickable.iter()
+        .map(|(e, p, s)| (e, at.distance(Vec2::from((p.x, p.y))), s.radius().max(MIN_PICK_RADIUS)))
+        .filter(|(_, d, r)| d < r)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(e, _, _)| e);
+}
+
+fn spawn_text(commands: &mut Commands, font: &UiFont) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.),
+                    left: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 16.,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(InspectorText);
+}
+
+fn update_inspector_text(
+    mut commands: Commands,
+    selected: Res<Selected>,
+    font: Option<Res<UiFont>>,
+    info: Query<(
+        &Position,
+        Option<&Health>,
+        Option<&Hunger>,
+        Option<&AntAI>,
+        Option<&CurrentAction>,
+        Option<&AntEating>,
+        Option<&FindFood>,
+        Option<&Food>,
+        Option<&Queen>,
+        Option<&Genome>,
+        Option<&Generation>,
+    )>,
+    mut texts: Query<&mut Text, With<InspectorText>>,
+) {
+    // The font is loaded alongside the camera
+    let mut text = match texts.iter_mut().next() {
+        Some(t) => t,
+        None => {
+            if let Some(font) = font {
+                spawn_text(&mut commands, &font);
+            }
+            return;
+        },
+    };
+
+    let lines = match selected.0.map(|e| (e, info.get(e))) {
+        Some((e, Ok((pos, health, hunger, ai, action, eating, find_food, food, queen, genome, generation)))) => {
+            let mut lines = Vec::new();
+            let kind = if queen.is_some() {
+                "Queen"
+            } else if food.is_some() {
+                "Food"
+            } else {
+                "Ant"
+            };
+            lines.push(format!("{} {:?}", kind, e));
+            lines.push(format!("Position: ({:.1}, {:.1})", pos.x, pos.y));
+            if let Some(h) = health {
+                lines.push(format!("Health: {:.0}%", h.pct * 100.));
+            }
+            if let Some(h) = hunger {
+                lines.push(format!("Hunger: {:.0}%", h.pct * 100.));
+            }
+            if let Some(a) = action {
+                lines.push(format!("Action: {:?}", a.action));
+            }
+            if let Some(ai) = ai {
+                lines.push(format!("Goal: {:?}", ai.ai));
+                lines.push(match ai.ai {
+                    AiGoal::Destination { .. } => "Goal time left: until reached".to_string(),
+                    _ if ai.duration >= ENDLESS_GOAL_SECONDS => "Goal time left: until changed".to_string(),
+                    _ => format!("Goal time left: {:.1}s", ai.duration),
+                });
+            }
+            if ai.is_some() {
+                lines.push(format!("Finding food: {}", find_food.is_some()));
+                lines.push(match eating {
+                    Some(eating) => format!("Eating: {:?}", eating.food_ent),
+                    None => "Eating: nothing".to_string(),
+                });
+            }
+            if let Some(f) = food {
+                lines.push(format!("Food: {:?}", f.kind));
+                lines.push(format!("Food left: {:.2}", f.quantity));
+                lines.push(format!("Freshness: {:.0}%{}", f.freshness * 100., if f.is_rotten() { " (rotten)" } else { "" }));
+            }
+            if let Some(g) = generation {
+                lines.push(format!("Generation: {}", g.0));
+            }
+            if let Some(g) = genome {
+                lines.push(format!("Speed: {:.1}, vision: {:.1}", g.speed, g.vision));
+                lines.push(format!("Hunger rate: {:.4}/s", g.hunger_rate));
+            }
+            lines
+        },
+        _ => Vec::new(),
+    };
+
+    text.sections[0].value = lines.join("\n");
+}
+
+fn update_highlight(
+    screen: Res<ArenaStats>,
+    selected: Res<Selected>,
+    targets: Query<(&Position, &Size, Option<&AntAI>)>,
+    mut q: QuerySet<(
+        QueryState<(&mut Transform, &mut Visibility), With<SelectionHighlight>>,
+        QueryState<(&mut Transform, &mut Visibility), With<DestinationLine>>,
+    )>,
+) {
+    let target = selected.0.and_then(|e| targets.get(e).ok());
+
+    for (mut transform, mut visibility) in q.q0().iter_mut() {
+        visibility.is_visible = target.is_some();
+        if let Some((pos, size, _)) = target {
+            *transform = square_transform(screen.to_world(pos), size.radius() * 2.5);
+        }
+    }
+
+    let dest = match target {
+        Some((pos, _, Some(AntAI { ai: AiGoal::Destination { dest }, .. }))) => Some((*pos, *dest)),
+        _ => None,
+    };
+    for (mut transform, mut visibility) in q.q1().iter_mut() {
+        visibility.is_visible = dest.is_some();
+        if let Some((from, to)) = dest {
+            *transform = line_transform(screen.to_world(&from), screen.to_world(&to), 1.5);
+        }
+    }
+}
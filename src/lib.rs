@@ -0,0 +1,44 @@
+//! Ant colony simulation as a set of Bevy plugins.
+//!
+//! Add `AntFarmPlugins` to an `App` alongside `DefaultPlugins`, or pick the plugins one by one.
+
+pub mod arena;
+pub mod camera;
+pub mod clock;
+pub mod stats;
+pub mod hud;
+pub mod debug;
+pub mod heatmap;
+pub mod minimap;
+pub mod sprites;
+pub mod gizmo;
+pub mod inspector;
+pub mod walls;
+pub mod ant;
+pub mod food;
+pub mod map;
+pub mod fog;
+pub mod steering;
+pub mod collision;
+pub mod behaviour;
+pub mod recruit;
+pub mod trophallaxis;
+pub mod plugins;
+pub mod rng;
+pub mod config;
+pub mod headless;
+pub mod sweep;
+pub mod genome;
+pub mod evolve;
+pub mod gym;
+pub mod remote;
+pub mod script;
+pub mod scenario;
+pub mod cli;
+
+pub use crate::arena::ArenaPlugin;
+pub use crate::ant::AntPlugin;
+pub use crate::food::FoodPlugin;
+pub use crate::fog::FogOfWarPlugin;
+pub use crate::walls::WallPlugin;
+pub use crate::plugins::AntFarmPlugins;
@@ -0,0 +1,169 @@
+use bevy::prelude::*;
+use crate::config::load_ron;
+use crate::food::{FoodKind, FoodPolicy};
+use crate::arena::*;
+use crate::rng::SimRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Where named maps live, relative to the working directory
+const MAP_DIR: &str = "assets/maps";
+// Name of the map to run, without the .ron
+const MAP_ENV: &str = "ANTFARM_MAP";
+const BUILTIN_DEFAULT: &str = include_str!("../assets/maps/default.ron");
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MapDef {
+    pub name: String,
+    #[serde(default)]
+    pub food: Vec<FoodPolicy>,
+    // Wall tiles as (col, row), on top of the arena border
+    #[serde(default)]
+    pub walls: Vec<(u32, u32)>,
+}
+
+impl Default for MapDef {
+    fn default() -> Self {
+        MapDef::from_ron(BUILTIN_DEFAULT).expect("built in default map")
+    }
+}
+
+impl MapDef {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_ron(path)
+    }
+
+    // A map from MAP_DIR by name
+    pub fn load_named(name: &str) -> Result<Self, String> {
+        let path: PathBuf = Path::new(MAP_DIR).join(format!("{}.ron", name));
+        Self::load(&path)
+    }
+
+    // A path to a .ron file, otherwise a name in MAP_DIR
+    pub fn resolve(name_or_path: &str) -> Result<Self, String> {
+        let path = Path::new(name_or_path);
+        if path.extension().map_or(false, |e| e == "ron") || path.exists() {
+            Self::load(path)
+        } else {
+            Self::load_named(name_or_path)
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()).map_err(|e| e.to_string())
+    }
+
+    // Start with count piles of food, replacing the map's own opening scatter
+    pub fn with_initial_food(mut self, count: usize) -> Self {
+        let kind = self.food.iter()
+            .find_map(|p| match p {
+                FoodPolicy::Initial { kind, .. } => Some(*kind),
+                _ => None,
+            })
+            .unwrap_or(FoodKind::Seed);
+        self.food.retain(|p| !matches!(p, FoodPolicy::Initial { .. }));
+        self.food.insert(0, FoodPolicy::Initial { count, kind });
+        self
+    }
+
+    // Random wall segments and food patches, keeping clear of where the colony starts.
+    // Draws from the simulation rng, so seed it first for a repeatable map.
+    pub fn generate(name: &str, obstacles: usize, patches: usize) -> Self {
+        let mut rng = SimRng;
+        let mut walls = Vec::new();
+        for _ in 0..obstacles {
+            let col = rng.gen_range(4, ARENA_WIDTH_TILES - 4);
+            let row = rng.gen_range(4, ARENA_HEIGHT_TILES - 4);
+            let length = rng.gen_range(4, 20);
+            let horizontal = rng.gen::<bool>();
+            for i in 0..length {
+                let tile = if horizontal { (col + i, row) } else { (col, row + i) };
+                let clear_of_nest = (tile.0 as f32 - NEST_TILE.0).hypot(tile.1 as f32 - NEST_TILE.1) > NEST_CLEARANCE;
+                if tile.0 < ARENA_WIDTH_TILES - 1 && tile.1 < ARENA_HEIGHT_TILES - 1 && clear_of_nest {
+                    walls.push(tile);
+                }
+            }
+        }
+
+        let mut food = vec![FoodPolicy::Initial { count: 20, kind: FoodKind::Seed }];
+        for _ in 0..patches {
+            food.push(FoodPolicy::Patch {
+                x: rng.gen_range(8., (ARENA_WIDTH_TILES - 8) as f32),
+                y: rng.gen_range(8., (ARENA_HEIGHT_TILES - 8) as f32),
+                radius: 5.,
+                capacity: 10.,
+                interval: 5.,
+                kind: if rng.gen_range(0, 4) == 0 { FoodKind::Sugar } else { FoodKind::Seed },
+            });
+        }
+
+        MapDef {
+            name: name.to_string(),
+            food,
+            walls,
+        }
+    }
+}
+
+// Where AntBundle puts the colony, in tiles, and how far generated walls stay from it
+const NEST_TILE: (f32, f32) = (25., 25.);
+const NEST_CLEARANCE: f32 = 10.;
+
+// The map name given by ANTFARM_MAP, if any
+pub fn env_map_name() -> Option<String> {
+    std::env::var(MAP_ENV).ok().filter(|name| !name.is_empty())
+}
+
+// The map named by ANTFARM_MAP, or the built in one
+fn selected_map() -> MapDef {
+    match env_map_name() {
+        Some(name) => match MapDef::load_named(&name) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("Couldn't load map {}, using default: {}", name, e);
+                MapDef::default()
+            },
+        },
+        None => MapDef::default(),
+    }
+}
+
+pub struct MapPlugin;
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        // Apps and AntFarmPlugins::map may have picked one already
+        if !app.world.contains_resource::<MapDef>() {
+            app.insert_resource(selected_map());
+        }
+        let name = &app.world.get_resource::<MapDef>().unwrap().name;
+        println!("Running map {}", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_default_parses() {
+        assert!(!MapDef::default().name.is_empty());
+    }
+
+    #[test]
+    fn shipped_maps_parse() {
+        let entries = std::fs::read_dir(MAP_DIR).unwrap();
+        let mut count = 0;
+        for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+            if path.extension().map_or(false, |e| e == "ron") {
+                MapDef::load(&path).unwrap();
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+    }
+}
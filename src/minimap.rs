@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use crate::arena::*;
+use crate::ant::{Ant, Queen};
+use crate::camera::{CameraControl, MainCamera};
+use crate::food::Food;
+use crate::fog::FogMap;
+use crate::plugins::RequireExt;
+
+// Arena tiles per minimap pixel
+const MINIMAP_TILES_PER_PIXEL: u32 = 2;
+const MINIMAP_WIDTH: u32 = ARENA_WIDTH_TILES / MINIMAP_TILES_PER_PIXEL;
+const MINIMAP_HEIGHT: u32 = ARENA_HEIGHT_TILES / MINIMAP_TILES_PER_PIXEL;
+// Screen pixels per minimap pixel
+const MINIMAP_SCALE: f32 = 3.;
+// Real seconds between redraws
+const REDRAW_INTERVAL: f32 = 0.25;
+
+const BACKGROUND: [u8; 4] = [20, 30, 25, 220];
+const FOG: [u8; 4] = [90, 90, 90, 255];
+const WALL: [u8; 4] = [230, 230, 230, 255];
+const FOOD: [u8; 4] = [255, 165, 0, 255];
+const ANT: [u8; 4] = [180, 180, 180, 255];
+const QUEEN: [u8; 4] = [255, 60, 200, 255];
+const VIEW: [u8; 4] = [255, 255, 0, 255];
+
+struct Minimap {
+    texture: Handle<Image>,
+    redraw: Timer,
+}
+
+#[derive(Component)]
+struct MinimapNode;
+
+pub struct MinimapPlugin;
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<ArenaStats>("MinimapPlugin", "ArenaPlugin")
+            .requires::<CameraControl>("MinimapPlugin", "CameraPlugin")
+            .requires::<Windows>("MinimapPlugin", "DefaultPlugins")
+            .add_startup_system(spawn_minimap)
+            .add_system(draw_minimap)
+            .add_system(minimap_click);
+    }
+}
+
+fn spawn_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let image = Image::new(
+        Extent3d {
+            width: MINIMAP_WIDTH,
+            height: MINIMAP_HEIGHT,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        BACKGROUND.repeat((MINIMAP_WIDTH * MINIMAP_HEIGHT) as usize),
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    let texture = images.add(image);
+
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(10.),
+                    right: Val::Px(10.),
+                    ..Default::default()
+                },
+                size: bevy::math::Size::new(
+                    Val::Px(MINIMAP_WIDTH as f32 * MINIMAP_SCALE),
+                    Val::Px(MINIMAP_HEIGHT as f32 * MINIMAP_SCALE),
+                ),
+                ..Default::default()
+            },
+            image: UiImage(texture.clone()),
+            ..Default::default()
+        })
+        .insert(Interaction::default())
+        .insert(MinimapNode);
+
+    commands.insert_resource(Minimap {
+        texture,
+        redraw: Timer::from_seconds(REDRAW_INTERVAL, true),
+    });
+}
+
+// Minimap pixel for an arena position, None off the map
+fn pixel_of(pos: &Position) -> Option<(u32, u32)> {
+    tile_of(pos).map(|(col, row)| (col / MINIMAP_TILES_PER_PIXEL, row / MINIMAP_TILES_PER_PIXEL))
+}
+
+// Image rows run top down, arena rows bottom up
+fn plot(data: &mut [u8], x: u32, y: u32, color: [u8; 4]) {
+    if x >= MINIMAP_WIDTH || y >= MINIMAP_HEIGHT {
+        return;
+    }
+    let i = (((MINIMAP_HEIGHT - 1 - y) * MINIMAP_WIDTH + x) * 4) as usize;
+    data[i..i + 4].copy_from_slice(&color);
+}
+
+fn draw_minimap(
+    time: Res<Time>,
+    screen: Res<ArenaStats>,
+    fog: Option<Res<FogMap>>,
+    minimap: Option<ResMut<Minimap>>,
+    mut images: ResMut<Assets<Image>>,
+    cameras: Query<&Transform, With<MainCamera>>,
+    walls: Query<(&Position, &CollisionGroups)>,
+    food: Query<&Position, With<Food>>,
+    ants: Query<(&Position, Option<&Queen>), With<Ant>>,
+) {
+    let mut minimap = match minimap {
+        Some(m) => m,
+        None => return,
+    };
+    if !minimap.redraw.tick(time.delta()).just_finished() {
+        return;
+    }
+    let image = match images.get_mut(&minimap.texture) {
+        Some(i) => i,
+        None => return,
+    };
+    let data = &mut image.data;
+
+    // A pixel stays fogged until any of its tiles has been seen
+    for y in 0..MINIMAP_HEIGHT {
+        for x in 0..MINIMAP_WIDTH {
+            let seen = (0..MINIMAP_TILES_PER_PIXEL).any(|dy| {
+                (0..MINIMAP_TILES_PER_PIXEL).any(|dx| {
+                    fog.as_ref().map_or(true, |f| {
+                        f.is_cleared(x * MINIMAP_TILES_PER_PIXEL + dx, y * MINIMAP_TILES_PER_PIXEL + dy)
+                    })
+                })
+            });
+            plot(data, x, y, if seen { BACKGROUND } else { FOG });
+        }
+    }
+
+    for (p, groups) in walls.iter() {
+        if groups.memberships & CollisionGroups::WALL != 0 {
+            if let Some((x, y)) = pixel_of(p) {
+                plot(data, x, y, WALL);
+            }
+        }
+    }
+    for p in food.iter() {
+        if let Some((x, y)) = pixel_of(p) {
+            plot(data, x, y, FOOD);
+        }
+    }
+    // Queens last so workers crowding round don't hide her
+    let mut queens = Vec::new();
+    for (p, opt_queen) in ants.iter() {
+        if let Some((x, y)) = pixel_of(p) {
+            if opt_queen.is_some() {
+                queens.push((x, y));
+            } else {
+                plot(data, x, y, ANT);
+            }
+        }
+    }
+    for (x, y) in queens.into_iter() {
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            plot(data, x + dx, y + dy, QUEEN);
+        }
+    }
+
+    // Outline what the main camera is looking at
+    if let Some(camera) = cameras.iter().next() {
+        let half_view = screen.window_size() / 2. * camera.scale.truncate();
+        let centre = camera.translation.truncate();
+        let corner = |world: Vec2| {
+            let p = screen.to_arena(world);
+            let pixel = |v: f32, max: u32| {
+                ((v / ARENA_TILE_SIDE + 0.5) / MINIMAP_TILES_PER_PIXEL as f32).clamp(0., (max - 1) as f32) as u32
+            };
+            (pixel(p.x, MINIMAP_WIDTH), pixel(p.y, MINIMAP_HEIGHT))
+        };
+        let (x0, y0) = corner(centre - half_view);
+        let (x1, y1) = corner(centre + half_view);
+        for x in x0..=x1 {
+            plot(data, x, y0, VIEW);
+            plot(data, x, y1, VIEW);
+        }
+        for y in y0..=y1 {
+            plot(data, x0, y, VIEW);
+            plot(data, x1, y, VIEW);
+        }
+    }
+}
+
+// Clicking or dragging on the minimap moves the main camera there
+fn minimap_click(
+    windows: Res<Windows>,
+    screen: Res<ArenaStats>,
+    mut control: ResMut<CameraControl>,
+    nodes: Query<(&Interaction, &Node, &GlobalTransform), With<MinimapNode>>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    let cursor = match windows.get_primary().and_then(|w| w.cursor_position()) {
+        Some(c) => c,
+        None => return,
+    };
+
+    for (interaction, node, global) in nodes.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        // UI and cursor coordinates both start bottom left
+        let bottom_left = global.translation.truncate() - node.size / 2.;
+        let fraction = ((cursor - bottom_left) / node.size).clamp(Vec2::ZERO, Vec2::ONE);
+        let target = screen.to_world(&Position {
+            x: fraction.x * ARENA_WIDTH_TILES as f32 * ARENA_TILE_SIDE - ARENA_TILE_SIDE / 2.,
+            y: fraction.y * ARENA_HEIGHT_TILES as f32 * ARENA_TILE_SIDE - ARENA_TILE_SIDE / 2.,
+        });
+
+        control.follow = false;
+        for mut transform in cameras.iter_mut() {
+            transform.translation.x = target.x;
+            transform.translation.y = target.y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_cover_several_tiles() {
+        let side = ARENA_TILE_SIDE;
+        assert_eq!(pixel_of(&Position { x: 0., y: 0. }), Some((0, 0)));
+        assert_eq!(pixel_of(&Position { x: side, y: side }), Some((0, 0)));
+        assert_eq!(pixel_of(&Position { x: 5. * side, y: 2. * side }), Some((2, 1)));
+        assert_eq!(pixel_of(&Position { x: -10. * side, y: 0. }), None);
+    }
+
+    #[test]
+    fn plot_flips_rows() {
+        let mut data = vec![0; (MINIMAP_WIDTH * MINIMAP_HEIGHT * 4) as usize];
+        plot(&mut data, 1, 0, WALL);
+        let bottom = (((MINIMAP_HEIGHT - 1) * MINIMAP_WIDTH + 1) * 4) as usize;
+        assert_eq!(data[bottom..bottom + 4], WALL);
+        assert_eq!(data.iter().filter(|b| **b != 0).count(), 4);
+    }
+
+    #[test]
+    fn plot_ignores_pixels_off_the_map() {
+        let mut data = vec![0; (MINIMAP_WIDTH * MINIMAP_HEIGHT * 4) as usize];
+        plot(&mut data, MINIMAP_WIDTH, 0, FOOD);
+        plot(&mut data, 0, MINIMAP_HEIGHT, FOOD);
+        assert!(data.iter().all(|b| *b == 0));
+    }
+}
@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use bevy::app::PluginGroupBuilder;
+use crate::arena::ArenaPlugin;
+use crate::ant::{AntParams, AntPlugin, ColonySpawner};
+use crate::behaviour::BehaviourPlugin;
+use crate::camera::CameraPlugin;
+use crate::clock::ClockPlugin;
+use crate::collision::CollisionPlugin;
+use crate::debug::DebugOverlayPlugin;
+use crate::fog::FogOfWarPlugin;
+use crate::genome::{Genome, GenomePlugin};
+use crate::food::FoodPlugin;
+use crate::heatmap::HeatmapPlugin;
+use crate::hud::HudPlugin;
+use crate::inspector::InspectorPlugin;
+use crate::map::{MapDef, MapPlugin};
+use crate::minimap::MinimapPlugin;
+use crate::recruit::RecruitPlugin;
+use crate::scenario::{Objective, ObjectivePlugin};
+use crate::script::{ScriptPlugin, ScriptSource};
+use crate::sprites::SpritePlugin;
+use crate::stats::StatsPlugin;
+use crate::trophallaxis::TrophallaxisPlugin;
+use crate::walls::{WallPlugin, WallSource};
+
+// The whole simulation.  Add it before DefaultPlugins, or MinimalPlugins when headless.
+//
+//     app.add_plugins(AntFarmPlugins::default().fog(false).walls(WallSource::Border))
+pub struct AntFarmPlugins {
+    fog: bool,
+    headless: bool,
+    walls: WallSource,
+    spawner: Option<ColonySpawner>,
+    map: Option<MapDef>,
+    params: Option<AntParams>,
+    genome: Option<Genome>,
+    script: Option<ScriptSource>,
+    objectives: Vec<Objective>,
+}
+
+impl Default for AntFarmPlugins {
+    fn default() -> Self {
+        AntFarmPlugins {
+            fog: true,
+            headless: false,
+            walls: WallSource::default(),
+            spawner: None,
+            map: None,
+            params: None,
+            genome: None,
+            script: None,
+            objectives: Vec::new(),
+        }
+    }
+}
+
+impl AntFarmPlugins {
+    pub fn fog(mut self, on: bool) -> Self {
+        self.fog = on;
+        self
+    }
+
+    // Leave out everything that needs a window, input or assets
+    pub fn headless(mut self, on: bool) -> Self {
+        self.headless = on;
+        self
+    }
+
+    pub fn walls(mut self, walls: WallSource) -> Self {
+        self.walls = walls;
+        self
+    }
+
+    // Replaces the starting colony of AntPlugin
+    pub fn spawner(mut self, spawner: ColonySpawner) -> Self {
+        self.spawner = Some(spawner);
+        self
+    }
+
+    // Instead of the one MapPlugin would pick
+    pub fn map(mut self, map: MapDef) -> Self {
+        self.map = Some(map);
+        self
+    }
+
+    pub fn params(mut self, params: AntParams) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    // Every worker's steering, foraging and vision, see genome.rs
+    pub fn genome(mut self, genome: Genome) -> Self {
+        self.genome = Some(genome);
+        self
+    }
+
+    // Scenario events and worker decisions, see script.rs
+    pub fn script(mut self, script: ScriptSource) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    // Tracked in ObjectiveTracker, and shown in the HUD
+    pub fn objectives(mut self, objectives: Vec<Objective>) -> Self {
+        self.objectives = objectives;
+        self
+    }
+}
+
+impl PluginGroup for AntFarmPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(SetupPlugin {
+                walls: self.walls.clone(),
+                spawner: self.spawner.clone(),
+                map: self.map.clone(),
+                params: self.params,
+                genome: self.genome,
+            })
+            .add(ClockPlugin)
+            .add(ArenaPlugin)
+            .add(MapPlugin)
+            .add(WallPlugin)
+            .add(AntPlugin)
+            .add(CollisionPlugin)
+            .add(BehaviourPlugin)
+            .add(GenomePlugin)
+            .add(FoodPlugin)
+            .add(RecruitPlugin)
+            .add(TrophallaxisPlugin)
+            .add(StatsPlugin);
+
+        if self.fog {
+            group.add(FogOfWarPlugin);
+        }
+
+        if let Some(script) = &self.script {
+            group.add(ScriptPlugin { script: script.clone() });
+        }
+
+        if !self.objectives.is_empty() {
+            group.add(ObjectivePlugin { objectives: self.objectives.clone() });
+        }
+
+        if !self.headless {
+            group
+                .add(CameraPlugin)
+                .add(InspectorPlugin)
+                .add(HudPlugin)
+                .add(DebugOverlayPlugin)
+                .add(HeatmapPlugin)
+                .add(MinimapPlugin)
+                .add(SpritePlugin);
+        }
+    }
+}
+
+// Hands the builder's choices to the plugins that read them.  They init
+// defaults themselves, which leaves these alone.
+struct SetupPlugin {
+    walls: WallSource,
+    spawner: Option<ColonySpawner>,
+    map: Option<MapDef>,
+    params: Option<AntParams>,
+    genome: Option<Genome>,
+}
+
+impl Plugin for SetupPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.walls.clone());
+        if let Some(spawner) = &self.spawner {
+            app.insert_resource(spawner.clone());
+        }
+        if let Some(map) = &self.map {
+            app.insert_resource(map.clone());
+        }
+        if let Some(params) = self.params {
+            app.insert_resource(params);
+        }
+        if let Some(genome) = self.genome {
+            app.insert_resource(genome);
+        }
+    }
+}
+
+struct Requirement {
+    plugin: &'static str,
+    resource: &'static str,
+    provider: &'static str,
+    present: fn(&World) -> bool,
+}
+
+// Everything plugins said they need from each other, checked once before startup
+#[derive(Default)]
+struct Requirements {
+    list: Vec<Requirement>,
+}
+
+pub trait RequireExt {
+    // plugin can't run without resource T, which provider adds
+    fn requires<T: Send + Sync + 'static>(&mut self, plugin: &'static str, provider: &'static str) -> &mut Self;
+}
+
+impl RequireExt for App {
+    fn requires<T: Send + Sync + 'static>(&mut self, plugin: &'static str, provider: &'static str) -> &mut Self {
+        if !self.world.contains_resource::<Requirements>() {
+            self
+                .init_resource::<Requirements>()
+                .add_startup_system_to_stage(StartupStage::PreStartup, check_requirements.exclusive_system());
+        }
+
+        let mut requirements = self.world.get_resource_mut::<Requirements>().unwrap();
+        requirements.list.push(Requirement {
+            plugin,
+            resource: std::any::type_name::<T>(),
+            provider,
+            present: |world| world.contains_resource::<T>(),
+        });
+        self
+    }
+}
+
+// Runs before any system can panic on a missing Res, and panics first explaining what to add
+fn check_requirements(world: &mut World) {
+    let world: &World = world;
+    let requirements = match world.get_resource::<Requirements>() {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut missing: Vec<String> = requirements.list.iter()
+        .filter(|r| !(r.present)(world))
+        .map(|r| format!("  {} needs {}, add {}", r.plugin, r.resource, r.provider))
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    missing.dedup();
+    panic!("Ant farm plugins are missing dependencies:\n{}", missing.join("\n"));
+}
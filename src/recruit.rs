@@ -0,0 +1,374 @@
+use bevy::prelude::*;
+use crate::clock::SimClock;
+use crate::arena::*;
+use crate::ant::*;
+use crate::food::Food;
+use crate::gym::Agent;
+use crate::plugins::RequireExt;
+use crate::rng::random;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Food left over once a forager is full decides how it recruits
+const TANDEM_QUANTITY: f32 = 1.0;
+const MASS_QUANTITY: f32 = 2.0;
+const MASS_RECRUIT_CHANCE: f32 = 0.6;
+
+// Ants close to done eating from a rich food remember it
+const RECRUIT_SATED: f32 = 0.95;
+
+// Distances in arena units
+const NEST_RANGE: f32 = 4. * ARENA_TILE_SIDE;
+const RECRUIT_RANGE: f32 = 8. * ARENA_TILE_SIDE;
+const TANDEM_FOLLOW_GAP: f32 = ARENA_TILE_SIDE;
+const TANDEM_MAX_GAP: f32 = 3. * ARENA_TILE_SIDE;
+const ARRIVAL_DIST: f32 = ARENA_TILE_SIDE;
+
+// Seconds
+const RECRUIT_PATIENCE: f32 = 20.;
+const TANDEM_TIMEOUT: f32 = 60.;
+
+// Shown in the HUD and kept in the run report
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct RecruitStats {
+    pub tandem_started: u32,
+    pub tandem_succeeded: u32,
+    pub tandem_failed: u32,
+    pub mass_recruited: u32,
+    pub mass_arrived: u32,
+    pub mass_lost: u32,
+}
+
+impl RecruitStats {
+    pub fn any(&self) -> bool {
+        self.tandem_started > 0 || self.mass_recruited > 0
+    }
+
+    pub fn tandem_success_rate(&self) -> f32 {
+        success_rate(self.tandem_succeeded, self.tandem_failed)
+    }
+
+    pub fn mass_success_rate(&self) -> f32 {
+        success_rate(self.mass_arrived, self.mass_lost)
+    }
+}
+
+impl fmt::Display for RecruitStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tandem {}/{} succeeded ({:.0}%), mass {}/{} arrived ({:.0}%)",
+            self.tandem_succeeded,
+            self.tandem_started,
+            self.tandem_success_rate() * 100.,
+            self.mass_arrived,
+            self.mass_recruited,
+            self.mass_success_rate() * 100.,
+        )
+    }
+}
+
+fn success_rate(succeeded: u32, failed: u32) -> f32 {
+    let finished = succeeded + failed;
+    if finished == 0 {
+        return 0.;
+    }
+    succeeded as f32 / finished as f32
+}
+
+// Heading back to the nest to tell others about food_ent
+#[derive(Component)]
+pub struct Recruiter {
+    pub food_ent: Entity,
+    patience: f32,
+}
+
+#[derive(Component)]
+pub struct TandemLeader {
+    pub follower: Entity,
+    pub food_ent: Entity,
+    elapsed: f32,
+}
+
+#[derive(Component)]
+pub struct TandemFollower {
+    pub leader: Entity,
+}
+
+#[derive(Component)]
+pub struct MassRecruit {
+    pub food_ent: Entity,
+}
+
+pub struct RecruitPlugin;
+impl Plugin for RecruitPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<SimClock>("RecruitPlugin", "ClockPlugin")
+            .requires::<KnownFood>("RecruitPlugin", "AntPlugin")
+            .insert_resource(RecruitStats::default())
+            .add_system_set(
+                SystemSet::new()
+                    .after(BigPhase::Act)
+                    .before(BigPhase::Ambient)
+                    .with_system(become_recruiter)
+                    .with_system(recruit_at_nest)
+                    .with_system(tandem_run)
+                    .with_system(mass_recruit_arrival)
+            );
+    }
+}
+
+fn become_recruiter(
+    mut commands: Commands,
+    food: Query<&Food>,
+    // The trainer's agent neither recruits nor gets recruited
+    eaters: Query<(Entity, &AntEating, &Hunger), (Without<Queen>, Without<Agent>, Without<Recruiter>, Without<TandemLeader>, Without<TandemFollower>)>,
+) {
+    for (e, eating, hunger) in eaters.iter() {
+        if hunger.pct < RECRUIT_SATED {
+            continue;
+        }
+        if let Ok(f) = food.get(eating.food_ent) {
+            if f.quantity >= TANDEM_QUANTITY {
+                commands.entity(e).insert(Recruiter {
+                    food_ent: eating.food_ent,
+                    patience: RECRUIT_PATIENCE,
+                });
+            }
+        }
+    }
+}
+
+fn recruit_at_nest(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    mut stats: ResMut<RecruitStats>,
+    food: Query<(&Position, &Food)>,
+    queens: Query<&Position, With<Queen>>,
+    mut recruiters: Query<(Entity, &Position, &mut AntAI, &mut Recruiter, Option<&FindFood>), (Without<AntEating>, Without<Queen>)>,
+    nestmates: Query<(Entity, &Position), (
+        With<Ant>,
+        Without<Queen>,
+        Without<Agent>,
+        Without<FindFood>,
+        Without<AntEating>,
+        Without<Recruiter>,
+        Without<TandemLeader>,
+        Without<TandemFollower>,
+        Without<MassRecruit>,
+    )>,
+) {
+    let dt = clock.delta_seconds();
+    let mut claimed = std::collections::HashSet::<Entity>::new();
+
+    for (e, pos, mut ai, mut recruiter, opt_find) in recruiters.iter_mut() {
+        recruiter.patience -= dt;
+
+        // Got hungry, ran out of time, or the food is already gone
+        let target = food.get(recruiter.food_ent);
+        let nest = queens.iter().next();
+        if opt_find.is_some() || recruiter.patience <= 0. || target.is_err() || nest.is_none() {
+            commands.entity(e).remove::<Recruiter>();
+            if opt_find.is_none() {
+                *ai = AntAI::default();
+            }
+            continue;
+        }
+        let (food_pos, target_food) = target.unwrap();
+        let nest = nest.unwrap();
+
+        let here = Vec2::from((pos.x, pos.y));
+        if here.distance(Vec2::from((nest.x, nest.y))) > NEST_RANGE {
+            ai.ai = AiGoal::Destination { dest: *nest };
+            ai.duration = 100000.;
+            continue;
+        }
+
+        // At the nest, wait for someone to tell
+        ai.ai = AiGoal::Wait;
+
+        let mut candidates: Vec<(Entity, f32)> = nestmates.iter()
+            .filter(|(n, _)| !claimed.contains(n))
+            .map(|(n, n_pos)| (n, here.distance(Vec2::from((n_pos.x, n_pos.y)))))
+            .filter(|(_, d)| *d < RECRUIT_RANGE)
+            .collect();
+
+        if candidates.is_empty() {
+            continue;
+        }
+
+        if target_food.quantity >= MASS_QUANTITY {
+            let mut recruited = 0;
+            for (n, _) in candidates.iter() {
+                if random::<f32>() >= MASS_RECRUIT_CHANCE {
+                    continue;
+                }
+                claimed.insert(*n);
+                commands.entity(*n)
+                    .insert(FindFood)
+                    .insert(MassRecruit { food_ent: recruiter.food_ent })
+                    .insert(AntAI {
+                        ai: AiGoal::Destination { dest: *food_pos },
+                        duration: 100000.,
+                    });
+                recruited += 1;
+            }
+            if recruited > 0 {
+                stats.mass_recruited += recruited;
+                commands.entity(e).remove::<Recruiter>();
+                *ai = AntAI::default();
+            }
+        } else {
+            candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            let follower = candidates[0].0;
+            claimed.insert(follower);
+
+            stats.tandem_started += 1;
+            commands.entity(follower).insert(TandemFollower { leader: e });
+            commands.entity(e)
+                .remove::<Recruiter>()
+                .insert(TandemLeader {
+                    follower,
+                    food_ent: recruiter.food_ent,
+                    elapsed: 0.,
+                });
+            ai.ai = AiGoal::Destination { dest: *food_pos };
+            ai.duration = 100000.;
+        }
+    }
+}
+
+fn tandem_run(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    mut stats: ResMut<RecruitStats>,
+    food: Query<&Position, With<Food>>,
+    mut leaders: Query<(Entity, &Position, &mut AntAI, &mut TandemLeader, Option<&FindFood>), Without<TandemFollower>>,
+    mut followers: Query<(Entity, &Position, &mut AntAI, &TandemFollower, Option<&FindFood>), (Without<TandemLeader>, Without<Agent>)>,
+) {
+    let dt = clock.delta_seconds();
+
+    // Followers whose leader died wander off
+    for (f, _, mut ai, follower, _) in followers.iter_mut() {
+        if leaders.get_mut(follower.leader).is_err() {
+            commands.entity(f).remove::<TandemFollower>();
+            *ai = AntAI::default();
+            stats.tandem_failed += 1;
+        }
+    }
+
+    for (l, l_pos, mut l_ai, mut leader, l_find) in leaders.iter_mut() {
+        leader.elapsed += dt;
+
+        let food_pos = food.get(leader.food_ent).ok().copied();
+        let follower_hungry = match followers.get_mut(leader.follower) {
+            Ok((_, _, _, _, f_find)) => f_find.is_some(),
+            Err(_) => true,
+        };
+
+        let abandoned = l_find.is_some() || food_pos.is_none() || leader.elapsed > TANDEM_TIMEOUT;
+        if abandoned || follower_hungry {
+            commands.entity(l).remove::<TandemLeader>();
+            if l_find.is_none() {
+                *l_ai = AntAI::default();
+            }
+            if let Ok((f, _, mut f_ai, _, f_find)) = followers.get_mut(leader.follower) {
+                commands.entity(f).remove::<TandemFollower>();
+                if f_find.is_none() {
+                    *f_ai = AntAI::default();
+                }
+            }
+            stats.tandem_failed += 1;
+            continue;
+        }
+        let food_pos = food_pos.unwrap();
+        let (f, f_pos, mut f_ai, _, _) = followers.get_mut(leader.follower).unwrap();
+
+        let leader_at = Vec2::from((l_pos.x, l_pos.y));
+        let follower_at = Vec2::from((f_pos.x, f_pos.y));
+        let gap = leader_at.distance(follower_at);
+
+        // Arrived with the follower in tow, hand it the food
+        if gap <= TANDEM_MAX_GAP && leader_at.distance(Vec2::from((food_pos.x, food_pos.y))) < ARRIVAL_DIST {
+            commands.entity(l).remove::<TandemLeader>();
+            *l_ai = AntAI::default();
+            commands.entity(f)
+                .remove::<TandemFollower>()
+                .insert(FindFood);
+            *f_ai = AntAI {
+                ai: AiGoal::Destination { dest: food_pos },
+                duration: 100000.,
+            };
+            stats.tandem_succeeded += 1;
+            continue;
+        }
+
+        // Leader waits up for a lagging follower
+        if gap > TANDEM_MAX_GAP {
+            l_ai.ai = AiGoal::Wait;
+        } else {
+            l_ai.ai = AiGoal::Destination { dest: food_pos };
+        }
+
+        if gap > TANDEM_FOLLOW_GAP {
+            f_ai.ai = AiGoal::Destination { dest: *l_pos };
+            f_ai.duration = 100000.;
+        } else {
+            f_ai.ai = AiGoal::Wait;
+        }
+    }
+}
+
+fn mass_recruit_arrival(
+    mut commands: Commands,
+    mut stats: ResMut<RecruitStats>,
+    food: Query<&Food>,
+    recruits: Query<(Entity, &MassRecruit, Option<&AntEating>, Option<&FindFood>)>,
+) {
+    for (e, recruit, opt_eating, opt_find) in recruits.iter() {
+        match (opt_eating, opt_find) {
+            (Some(eating), _) => {
+                if eating.food_ent == recruit.food_ent {
+                    stats.mass_arrived += 1;
+                } else {
+                    stats.mass_lost += 1;
+                }
+            },
+            // Still on the way
+            (None, Some(_)) if food.get(recruit.food_ent).is_ok() => continue,
+            _ => stats.mass_lost += 1,
+        }
+        commands.entity(e).remove::<MassRecruit>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_count_finished_runs_only() {
+        let stats = RecruitStats {
+            tandem_started: 5,
+            tandem_succeeded: 3,
+            tandem_failed: 1,
+            ..Default::default()
+        };
+        assert_eq!(stats.tandem_success_rate(), 0.75);
+        assert_eq!(stats.mass_success_rate(), 0.);
+        assert!(stats.any());
+        assert!(!RecruitStats::default().any());
+    }
+
+    #[test]
+    fn summary() {
+        let stats = RecruitStats {
+            mass_recruited: 4,
+            mass_arrived: 1,
+            mass_lost: 1,
+            ..Default::default()
+        };
+        assert_eq!(stats.to_string(), "tandem 0/0 succeeded (0%), mass 1/4 arrived (50%)");
+    }
+}
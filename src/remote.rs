@@ -0,0 +1,498 @@
+use bevy::prelude::*;
+use bevy::ecs::event::ManualEventReader;
+use crate::arena::*;
+use crate::ant::*;
+use crate::clock::SimClock;
+use crate::fog::FogClearedEvent;
+use crate::food::{Food, FoodCreateEvent, FoodKind, FOOD_FULL};
+use crate::plugins::RequireExt;
+use crate::recruit::RecruitStats;
+use crate::stats::ColonyStats;
+use crate::walls::spawn_tile;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// Lines queued for a client before it counts as fallen behind and is dropped
+const OUTBOX_LINES: usize = 1024;
+// A client that won't take a line for this long is gone
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStream {
+    Deaths,
+    FoodDiscovered,
+    FogCleared,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+    Ants,
+    Food,
+    Walls,
+    Stats,
+}
+
+fn one() -> usize {
+    1
+}
+
+fn one_tick() -> u32 {
+    1
+}
+
+fn full_pile() -> f32 {
+    FOOD_FULL
+}
+
+fn seed_kind() -> FoodKind {
+    FoodKind::Seed
+}
+
+// One JSON object per line, such as {"cmd": "drop_food", "x": 80, "y": 80}.
+// Positions are arena units, tiles are (col, row).  Every request gets one
+// line back, with "error" in it if it failed.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum RemoteRequest {
+    SpawnAnts {
+        x: f32,
+        y: f32,
+        #[serde(default = "one")]
+        count: usize,
+    },
+    DropFood {
+        x: f32,
+        y: f32,
+        #[serde(default = "full_pile")]
+        quantity: f32,
+        #[serde(default = "seed_kind")]
+        kind: FoodKind,
+    },
+    ToggleWall {
+        col: u32,
+        row: u32,
+    },
+    Pause {
+        paused: bool,
+    },
+    // Runs this many updates then stays paused
+    Step {
+        #[serde(default = "one_tick")]
+        ticks: u32,
+    },
+    Query {
+        what: QueryKind,
+    },
+    Subscribe {
+        events: Vec<EventStream>,
+    },
+    Unsubscribe {
+        events: Vec<EventStream>,
+    },
+}
+
+// From the network threads to the simulation
+enum Incoming {
+    Connected(u32, SyncSender<String>),
+    Request(u32, Result<RemoteRequest, String>),
+    Disconnected(u32),
+}
+
+struct RemoteInbox(Mutex<Receiver<Incoming>>);
+
+// Writing is left to a thread per client, so a slow one can't hold up the simulation
+struct RemoteClient {
+    outbox: SyncSender<String>,
+    events: HashSet<EventStream>,
+}
+
+#[derive(Default)]
+struct RemoteClients {
+    clients: HashMap<u32, RemoteClient>,
+    // A client paused or stepped the clock, which runs again once they've all gone
+    holding_clock: bool,
+}
+
+impl RemoteClients {
+    // Clients that have gone or fallen behind are dropped
+    fn send(&mut self, id: u32, message: &Value) {
+        let failed = match self.clients.get(&id) {
+            Some(client) => match client.outbox.try_send(message.to_string()) {
+                Ok(()) => false,
+                Err(TrySendError::Full(_)) => {
+                    println!("Remote client {} fell behind, dropping it", id);
+                    true
+                },
+                Err(TrySendError::Disconnected(_)) => true,
+            },
+            None => false,
+        };
+        if failed {
+            self.clients.remove(&id);
+        }
+    }
+
+    fn broadcast(&mut self, stream: EventStream, message: &Value) {
+        let ids: Vec<u32> = self.clients.iter()
+            .filter(|(_, c)| c.events.contains(&stream))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids.into_iter() {
+            self.send(id, message);
+        }
+    }
+}
+
+// Drive and watch the simulation from other programs over 127.0.0.1:port.
+// Works the same with or without a window.
+pub struct RemotePlugin {
+    port: u16,
+    // Bound up front so a port that's taken fails the run, build takes it
+    listener: Mutex<Option<TcpListener>>,
+}
+
+impl RemotePlugin {
+    // Port 0 picks a free one
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Couldn't listen for remote control on port {}: {}", port, e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        Ok(RemotePlugin { port, listener: Mutex::new(Some(listener)) })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Plugin for RemotePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+        if let Some(listener) = self.listener.lock().unwrap().take() {
+            println!("Remote control listening on 127.0.0.1:{}", self.port);
+            thread::spawn(move || accept_clients(listener, sender));
+        }
+
+        app
+            .requires::<SimClock>("RemotePlugin", "ClockPlugin")
+            .requires::<ColonyStats>("RemotePlugin", "StatsPlugin")
+            .requires::<Events<FoodCreateEvent>>("RemotePlugin", "FoodPlugin")
+            .requires::<Events<AntDeathEvent>>("RemotePlugin", "AntPlugin")
+            .requires::<Events<FoodDiscoveredEvent>>("RemotePlugin", "AntPlugin")
+            .insert_resource(RemoteInbox(Mutex::new(receiver)))
+            .init_resource::<RemoteClients>()
+            .add_system(remote_requests
+                .before(BigPhase::Decide)
+            )
+            .add_system(remote_events
+                .after(BigPhase::Cleanup)
+            );
+    }
+}
+
+// Each client gets a thread reading its requests and one writing its replies
+fn accept_clients(listener: TcpListener, inbox: Sender<Incoming>) {
+    for (id, stream) in (0..).zip(listener.incoming()) {
+        let (reader, writer) = match stream.and_then(|s| s.try_clone().map(|w| (s, w))) {
+            Ok(pair) => pair,
+            Err(_) => continue,
+        };
+        let (outbox, lines) = sync_channel(OUTBOX_LINES);
+        // The app has gone
+        if inbox.send(Incoming::Connected(id, outbox)).is_err() {
+            return;
+        }
+        thread::spawn(move || write_client(writer, lines));
+
+        let inbox = inbox.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(_) => break,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let request = serde_json::from_str(&line).map_err(|e| e.to_string());
+                if inbox.send(Incoming::Request(id, request)).is_err() {
+                    return;
+                }
+            }
+            let _ = inbox.send(Incoming::Disconnected(id));
+        });
+    }
+}
+
+// Until the client is dropped or stops taking lines, then hangs up on it
+fn write_client(mut stream: TcpStream, lines: Receiver<String>) {
+    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok() {
+        for line in lines.iter() {
+            if writeln!(stream, "{}", line).is_err() {
+                break;
+            }
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn remote_requests(
+    mut commands: Commands,
+    inbox: Res<RemoteInbox>,
+    mut clients: ResMut<RemoteClients>,
+    mut clock: ResMut<SimClock>,
+    stats: Res<ColonyStats>,
+    recruitment: Option<Res<RecruitStats>>,
+    mut food_writer: EventWriter<FoodCreateEvent>,
+    ants: Query<(Entity, &Position, &Health, &Hunger, Option<&Queen>), With<Ant>>,
+    food: Query<(Entity, &Position, &Food)>,
+    solids: Query<(Entity, &Position, &CollisionGroups)>,
+) {
+    let incoming: Vec<Incoming> = inbox.0.lock().unwrap().try_iter().collect();
+    for message in incoming.into_iter() {
+        let (id, request) = match message {
+            Incoming::Connected(id, outbox) => {
+                clients.clients.insert(id, RemoteClient {
+                    outbox,
+                    events: HashSet::new(),
+                });
+                continue;
+            },
+            Incoming::Disconnected(id) => {
+                clients.clients.remove(&id);
+                continue;
+            },
+            Incoming::Request(id, request) => (id, request),
+        };
+
+        let request = match request {
+            Ok(r) => r,
+            Err(error) => {
+                clients.send(id, &json!({ "error": error }));
+                continue;
+            },
+        };
+        let response = match request {
+            RemoteRequest::SpawnAnts { x, y, count } => {
+                let ids: Vec<u64> = (0..count)
+                    .map(|_| commands.spawn_bundle(AntBundle::new(x, y)).id().to_bits())
+                    .collect();
+                json!({ "ok": true, "ids": ids })
+            },
+            RemoteRequest::DropFood { x, y, quantity, kind } => {
+                food_writer.send(FoodCreateEvent { x, y, quantity, kind });
+                json!({ "ok": true })
+            },
+            RemoteRequest::ToggleWall { col, row } => {
+                if col >= ARENA_WIDTH_TILES || row >= ARENA_HEIGHT_TILES {
+                    json!({ "error": "No such tile" })
+                } else {
+                    let existing = solids.iter()
+                        .filter(|(_, _, g)| g.memberships & CollisionGroups::WALL != 0)
+                        .find(|(_, p, _)| tile_of(p) == Some((col, row)));
+                    match existing {
+                        Some((wall, _, _)) => {
+                            commands.entity(wall).despawn();
+                            json!({ "ok": true, "wall": false })
+                        },
+                        None => {
+                            spawn_tile(&mut commands, ARENA_TILE_SIDE * col as f32, ARENA_TILE_SIDE * row as f32);
+                            json!({ "ok": true, "wall": true })
+                        },
+                    }
+                }
+            },
+            RemoteRequest::Pause { paused } => {
+                clock.paused = paused;
+                clients.holding_clock = paused;
+                json!({ "ok": true, "paused": paused })
+            },
+            RemoteRequest::Step { ticks } => {
+                clock.paused = true;
+                clock.step(ticks);
+                clients.holding_clock = true;
+                json!({ "ok": true })
+            },
+            RemoteRequest::Query { what } => match what {
+                QueryKind::Ants => {
+                    let list: Vec<Value> = ants.iter()
+                        .map(|(e, p, health, hunger, queen)| json!({
+                            "id": e.to_bits(),
+                            "x": p.x,
+                            "y": p.y,
+                            "health": health.pct,
+                            "hunger": hunger.pct,
+                            "queen": queen.is_some(),
+                        }))
+                        .collect();
+                    json!({ "ok": true, "ants": list })
+                },
+                QueryKind::Food => {
+                    let list: Vec<Value> = food.iter()
+                        .map(|(e, p, f)| json!({
+                            "id": e.to_bits(),
+                            "x": p.x,
+                            "y": p.y,
+                            "quantity": f.quantity,
+                            "kind": f.kind,
+                            "rotten": f.is_rotten(),
+                        }))
+                        .collect();
+                    json!({ "ok": true, "food": list })
+                },
+                QueryKind::Walls => {
+                    let list: Vec<(u32, u32)> = solids.iter()
+                        .filter(|(_, _, g)| g.memberships & CollisionGroups::WALL != 0)
+                        .filter_map(|(_, p, _)| tile_of(p))
+                        .collect();
+                    json!({ "ok": true, "walls": list })
+                },
+                QueryKind::Stats => json!({
+                    "ok": true,
+                    "time": clock.elapsed_seconds(),
+                    "paused": clock.paused,
+                    "population": stats.population,
+                    "peak_population": stats.peak_population,
+                    "deaths": stats.deaths,
+                    "births": stats.births,
+                    "food_in_world": stats.food_in_world,
+                    "food_eaten": stats.food_eaten,
+                    "fog_uncovered": stats.fog_uncovered,
+                    "recruitment": recruitment.as_deref().copied().unwrap_or_default(),
+                }),
+            },
+            RemoteRequest::Subscribe { events } => {
+                if let Some(client) = clients.clients.get_mut(&id) {
+                    client.events.extend(events.into_iter());
+                }
+                json!({ "ok": true })
+            },
+            RemoteRequest::Unsubscribe { events } => {
+                if let Some(client) = clients.clients.get_mut(&id) {
+                    for stream in events.iter() {
+                        client.events.remove(stream);
+                    }
+                }
+                json!({ "ok": true })
+            },
+        };
+        clients.send(id, &response);
+    }
+
+    // Nobody left to unpause a run, headless ones especially
+    if clients.holding_clock && clients.clients.is_empty() {
+        clients.holding_clock = false;
+        clock.paused = false;
+        println!("Last remote client gone, running again");
+    }
+}
+
+// Fog is optional, so its events are read by hand
+fn remote_events(
+    mut clients: ResMut<RemoteClients>,
+    mut deaths: EventReader<AntDeathEvent>,
+    mut discovered: EventReader<FoodDiscoveredEvent>,
+    fog_cleared: Option<Res<Events<FogClearedEvent>>>,
+    mut fog_reader: Local<ManualEventReader<FogClearedEvent>>,
+    positions: Query<&Position>,
+) {
+    for death in deaths.iter() {
+        let at = positions.get(death.ent).ok();
+        clients.broadcast(EventStream::Deaths, &json!({
+            "event": "death",
+            "id": death.ent.to_bits(),
+            "x": at.map(|p| p.x),
+            "y": at.map(|p| p.y),
+        }));
+    }
+    for found in discovered.iter() {
+        clients.broadcast(EventStream::FoodDiscovered, &json!({
+            "event": "food_discovered",
+            "id": found.food.to_bits(),
+            "ant": found.ant.to_bits(),
+            "x": found.at.x,
+            "y": found.at.y,
+        }));
+    }
+    if let Some(events) = fog_cleared {
+        for cleared in fog_reader.iter(&events) {
+            clients.broadcast(EventStream::FogCleared, &json!({
+                "event": "fog_cleared",
+                "tiles": cleared.tiles,
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimConfig;
+    use crate::headless;
+
+    // Updates the app until the next line comes back
+    fn reply(app: &mut App, reader: &mut BufReader<TcpStream>) -> Value {
+        let mut line = String::new();
+        for _ in 0..200 {
+            app.update();
+            let _ = reader.read_line(&mut line);
+            if line.ends_with('\n') {
+                return serde_json::from_str(&line).unwrap();
+            }
+        }
+        panic!("No reply, only {:?}", line);
+    }
+
+    #[test]
+    fn serves_requests_over_tcp() {
+        let plugin = RemotePlugin::bind(0).unwrap();
+        let port = plugin.port();
+        let mut app = headless::headless_app(&SimConfig::default()).unwrap();
+        app.add_plugin(plugin);
+
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+
+        writeln!(writer, r#"{{"cmd": "query", "what": "stats"}}"#).unwrap();
+        let stats = reply(&mut app, &mut reader);
+        assert_eq!(stats["ok"], true);
+        assert!(stats["time"].is_number());
+
+        writeln!(writer, r#"{{"cmd": "drop_food", "x": 80, "y": 96}}"#).unwrap();
+        assert_eq!(reply(&mut app, &mut reader)["ok"], true);
+        writeln!(writer, r#"{{"cmd": "query", "what": "food"}}"#).unwrap();
+        let food = reply(&mut app, &mut reader);
+        assert!(food["food"].as_array().unwrap().iter().any(|f| f["x"] == 80. && f["y"] == 96.));
+
+        writeln!(writer, r#"{{"cmd": "subscribe", "events": ["deaths"]}}"#).unwrap();
+        assert_eq!(reply(&mut app, &mut reader)["ok"], true);
+        writeln!(writer, r#"{{"cmd": "query", "what": "ants"}}"#).unwrap();
+        let ants = reply(&mut app, &mut reader);
+        let id = ants["ants"][0]["id"].as_u64().unwrap();
+        app.world.get_resource_mut::<Events<AntDeathEvent>>().unwrap()
+            .send(AntDeathEvent { ent: Entity::from_bits(id) });
+        let death = reply(&mut app, &mut reader);
+        assert_eq!(death["event"], "death");
+        assert_eq!(death["id"], id);
+
+        writeln!(writer, "not json").unwrap();
+        assert!(reply(&mut app, &mut reader)["error"].is_string());
+    }
+
+    #[test]
+    fn taken_port_is_an_error() {
+        let plugin = RemotePlugin::bind(0).unwrap();
+        assert!(RemotePlugin::bind(plugin.port()).is_err());
+    }
+}
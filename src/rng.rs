@@ -0,0 +1,47 @@
+use rand::distributions::{Distribution, Standard};
+use rand::rngs::StdRng;
+use rand::{Error, Rng, RngCore, SeedableRng};
+use std::sync::Mutex;
+
+// One generator for the whole simulation so a seed reproduces a run.
+// Unseeded runs draw a seed from the OS the first time it's used.
+static SIM_RNG: Mutex<Option<StdRng>> = Mutex::new(None);
+
+pub fn seed(seed: u64) {
+    *SIM_RNG.lock().unwrap() = Some(StdRng::seed_from_u64(seed));
+}
+
+fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    let mut guard = SIM_RNG.lock().unwrap();
+    f(guard.get_or_insert_with(StdRng::from_entropy))
+}
+
+// Stands in for thread_rng(), drawing from the shared simulation generator
+#[derive(Clone, Copy, Default)]
+pub struct SimRng;
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        with_rng(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        with_rng(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        with_rng(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        with_rng(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+// Stands in for rand::random()
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    SimRng.gen()
+}
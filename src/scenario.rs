@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use crate::clock::SimClock;
+use crate::config::{load_ron, SimConfig};
+use crate::plugins::RequireExt;
+use crate::stats::ColonyStats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Where named scenarios live, relative to the working directory.  Every
+// scenario in it is part of `antfarm scenarios`.
+const SCENARIO_DIR: &str = "assets/scenarios";
+
+// What a scenario asks of the colony.  Times are simulated seconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Objective {
+    // Eat this much food, before within if given
+    CollectFood {
+        amount: f32,
+        #[serde(default)]
+        within: Option<f32>,
+    },
+    // Still have ants after this long
+    Survive { seconds: f32 },
+    // Uncover this fraction of the fog, before within if given
+    UncoverFog {
+        fraction: f32,
+        #[serde(default)]
+        within: Option<f32>,
+    },
+}
+
+fn minutes(seconds: f64) -> String {
+    let seconds = seconds.max(0.) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let within = |w: &Option<f32>| w.map_or(String::new(), |w| format!(" in {}", minutes(w as f64)));
+        match self {
+            Objective::CollectFood { amount, within: w } => write!(f, "Collect {} food{}", amount, within(w)),
+            Objective::Survive { seconds } => write!(f, "Survive {}", minutes(*seconds as f64)),
+            Objective::UncoverFog { fraction, within: w } => write!(f, "Uncover {:.0}% of the fog{}", fraction * 100., within(w)),
+        }
+    }
+}
+
+impl Objective {
+    // Decided objectives stay decided, see ObjectiveTracker
+    pub fn check(&self, stats: &ColonyStats, elapsed: f64) -> ObjectiveStatus {
+        let late = |within: &Option<f32>| within.map_or(false, |w| elapsed > w as f64);
+        let dead = stats.population == 0;
+        let (met, failed) = match self {
+            // Reaching it after within is still too late
+            Objective::CollectFood { amount, within } => (stats.food_eaten >= *amount && !late(within), late(within) || dead),
+            Objective::Survive { seconds } => (!dead && elapsed >= *seconds as f64, dead),
+            Objective::UncoverFog { fraction, within } => (stats.fog_uncovered >= *fraction && !late(within), late(within) || dead),
+        };
+        if met {
+            ObjectiveStatus::Met
+        } else if failed {
+            ObjectiveStatus::Failed
+        } else {
+            ObjectiveStatus::Pending
+        }
+    }
+
+    pub fn progress(&self, stats: &ColonyStats, elapsed: f64) -> String {
+        match self {
+            Objective::CollectFood { amount, .. } => format!("{:.1}/{}", stats.food_eaten, amount),
+            Objective::Survive { seconds } => format!("{}/{}", minutes(elapsed), minutes(*seconds as f64)),
+            Objective::UncoverFog { fraction, .. } => format!("{:.0}/{:.0}%", stats.fog_uncovered * 100., fraction * 100.),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ObjectiveStatus {
+    Pending,
+    Met,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrackedObjective {
+    pub objective: Objective,
+    pub status: ObjectiveStatus,
+}
+
+// How the run is doing against its objectives
+#[derive(Clone, Debug, Default)]
+pub struct ObjectiveTracker {
+    pub objectives: Vec<TrackedObjective>,
+}
+
+impl ObjectiveTracker {
+    pub fn new(objectives: &[Objective]) -> Self {
+        ObjectiveTracker {
+            objectives: objectives.iter()
+                .map(|o| TrackedObjective {
+                    objective: o.clone(),
+                    status: ObjectiveStatus::Pending,
+                })
+                .collect(),
+        }
+    }
+
+    // Met once every objective is, failed as soon as any one is
+    pub fn status(&self) -> ObjectiveStatus {
+        let statuses = || self.objectives.iter().map(|o| o.status);
+        if statuses().any(|s| s == ObjectiveStatus::Failed) {
+            ObjectiveStatus::Failed
+        } else if statuses().all(|s| s == ObjectiveStatus::Met) {
+            ObjectiveStatus::Met
+        } else {
+            ObjectiveStatus::Pending
+        }
+    }
+
+    // The run is over, so anything not met by now never will be
+    pub fn finish(&mut self) {
+        for tracked in self.objectives.iter_mut() {
+            if tracked.status == ObjectiveStatus::Pending {
+                tracked.status = ObjectiveStatus::Failed;
+            }
+        }
+    }
+}
+
+pub struct ObjectivePlugin {
+    pub objectives: Vec<Objective>,
+}
+
+impl Plugin for ObjectivePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<SimClock>("ObjectivePlugin", "ClockPlugin")
+            .requires::<ColonyStats>("ObjectivePlugin", "StatsPlugin")
+            .insert_resource(ObjectiveTracker::new(&self.objectives))
+            // After the stats are up to date
+            .add_system_to_stage(CoreStage::PostUpdate, track_objectives);
+    }
+}
+
+fn track_objectives(
+    clock: Res<SimClock>,
+    stats: Res<ColonyStats>,
+    mut tracker: ResMut<ObjectiveTracker>,
+) {
+    let before = tracker.status();
+    if before != ObjectiveStatus::Pending {
+        return;
+    }
+
+    let elapsed = clock.elapsed_seconds();
+    for tracked in tracker.objectives.iter_mut() {
+        if tracked.status == ObjectiveStatus::Pending {
+            tracked.status = tracked.objective.check(&stats, elapsed);
+            if tracked.status != ObjectiveStatus::Pending {
+                println!("{}: {:?} at {}", tracked.objective, tracked.status, minutes(elapsed));
+            }
+        }
+    }
+    match tracker.status() {
+        ObjectiveStatus::Met => println!("Scenario won"),
+        ObjectiveStatus::Failed => println!("Scenario lost"),
+        ObjectiveStatus::Pending => (),
+    }
+}
+
+// A run with objectives to meet: its config sets the map, colony and food policy
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub config: SimConfig,
+}
+
+impl Scenario {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_ron(path)
+    }
+
+    // A path to a .ron file, otherwise a name in SCENARIO_DIR
+    pub fn resolve(name_or_path: &str) -> Result<Self, String> {
+        let path = Path::new(name_or_path);
+        if path.extension().map_or(false, |e| e == "ron") || path.exists() {
+            Self::load(path)
+        } else {
+            Self::load(&Path::new(SCENARIO_DIR).join(format!("{}.ron", name_or_path)))
+        }
+    }
+
+    // Every scenario in SCENARIO_DIR, by file name
+    pub fn builtin() -> Result<Vec<(PathBuf, Scenario)>, String> {
+        let entries = std::fs::read_dir(SCENARIO_DIR).map_err(|e| format!("{}: {}", SCENARIO_DIR, e))?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map_or(false, |e| e == "ron"))
+            .collect();
+        paths.sort();
+        paths.into_iter()
+            .map(|path| Self::load(&path).map(|scenario| (path, scenario)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(population: usize, food_eaten: f32, fog_uncovered: f32) -> ColonyStats {
+        // Some of its fields are private to stats
+        let mut stats = ColonyStats::default();
+        stats.population = population;
+        stats.food_eaten = food_eaten;
+        stats.fog_uncovered = fog_uncovered;
+        stats
+    }
+
+    fn tracker(statuses: &[ObjectiveStatus]) -> ObjectiveTracker {
+        ObjectiveTracker {
+            objectives: statuses.iter()
+                .map(|status| TrackedObjective {
+                    objective: Objective::Survive { seconds: 60. },
+                    status: *status,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn collect_food_met_in_time() {
+        let objective = Objective::CollectFood { amount: 5., within: Some(60.) };
+        assert_eq!(objective.check(&stats(10, 5., 0.), 30.), ObjectiveStatus::Met);
+        assert_eq!(objective.check(&stats(10, 4., 0.), 30.), ObjectiveStatus::Pending);
+    }
+
+    #[test]
+    fn collect_food_too_late() {
+        let objective = Objective::CollectFood { amount: 5., within: Some(60.) };
+        assert_eq!(objective.check(&stats(10, 4., 0.), 61.), ObjectiveStatus::Failed);
+        assert_eq!(objective.check(&stats(10, 5., 0.), 61.), ObjectiveStatus::Failed);
+    }
+
+    #[test]
+    fn death_before_within_fails() {
+        let objective = Objective::CollectFood { amount: 5., within: Some(60.) };
+        assert_eq!(objective.check(&stats(0, 1., 0.), 10.), ObjectiveStatus::Failed);
+        let objective = Objective::UncoverFog { fraction: 0.5, within: Some(60.) };
+        assert_eq!(objective.check(&stats(0, 0., 0.1), 10.), ObjectiveStatus::Failed);
+    }
+
+    #[test]
+    fn no_within_never_late() {
+        let objective = Objective::UncoverFog { fraction: 0.5, within: None };
+        assert_eq!(objective.check(&stats(10, 0., 0.1), 1e6), ObjectiveStatus::Pending);
+        assert_eq!(objective.check(&stats(10, 0., 0.5), 1e6), ObjectiveStatus::Met);
+    }
+
+    #[test]
+    fn survive_needs_ants_at_the_end() {
+        let objective = Objective::Survive { seconds: 60. };
+        assert_eq!(objective.check(&stats(10, 0., 0.), 59.), ObjectiveStatus::Pending);
+        assert_eq!(objective.check(&stats(10, 0., 0.), 60.), ObjectiveStatus::Met);
+        assert_eq!(objective.check(&stats(0, 0., 0.), 30.), ObjectiveStatus::Failed);
+        assert_eq!(objective.check(&stats(0, 0., 0.), 60.), ObjectiveStatus::Failed);
+    }
+
+    #[test]
+    fn failed_wins_over_met() {
+        use ObjectiveStatus::*;
+        assert_eq!(tracker(&[Met, Failed]).status(), Failed);
+        assert_eq!(tracker(&[Met, Pending, Failed]).status(), Failed);
+        assert_eq!(tracker(&[Met, Pending]).status(), Pending);
+        assert_eq!(tracker(&[Met, Met]).status(), Met);
+    }
+
+    #[test]
+    fn finish_fails_pending() {
+        use ObjectiveStatus::*;
+        let mut tracker = tracker(&[Met, Pending]);
+        tracker.finish();
+        let statuses: Vec<ObjectiveStatus> = tracker.objectives.iter().map(|o| o.status).collect();
+        assert_eq!(statuses, vec![Met, Failed]);
+        assert_eq!(tracker.status(), Failed);
+    }
+
+    #[test]
+    fn finish_keeps_met() {
+        let mut tracker = tracker(&[ObjectiveStatus::Met]);
+        tracker.finish();
+        assert_eq!(tracker.status(), ObjectiveStatus::Met);
+    }
+}
@@ -0,0 +1,409 @@
+use bevy::prelude::*;
+use crate::ant::*;
+use crate::clock::SimClock;
+use crate::config::read_text;
+use crate::food::{FoodCreateEvent, FoodKind};
+use crate::gym::Agent;
+use crate::plugins::RequireExt;
+use crate::rng::SimRng;
+use crate::stats::ColonyStats;
+use rand::Rng;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const SCRIPT_DIR: &str = "assets/scripts";
+
+// A goal the script picked lasts until it picks another
+const SCRIPT_GOAL_SECONDS: f32 = 100000.;
+
+// A script's source, checked to compile.  The file can define either or both of
+//
+//     fn tick() { ... }       every update, for scenario events
+//     fn decide(ant) { ... }  every update for each worker, returning a goal
+//                             name, a #{x, y} to head for, or () to leave it be
+//
+// Both run with `this` bound to a map kept between calls, for the script's own state.
+#[derive(Clone, Debug)]
+pub struct ScriptSource {
+    pub name: String,
+    pub source: String,
+}
+
+impl ScriptSource {
+    pub fn new(name: &str, source: &str) -> Result<Self, String> {
+        Engine::new()
+            .compile(source)
+            .map_err(|e| format!("{}: {}", name, e))?;
+        Ok(ScriptSource {
+            name: name.to_string(),
+            source: source.to_string(),
+        })
+    }
+
+    // A path to a .rhai file, otherwise a name in SCRIPT_DIR
+    pub fn resolve(name_or_path: &str) -> Result<Self, String> {
+        let path = Path::new(name_or_path);
+        let path = if path.extension().map_or(false, |e| e == "rhai") || path.exists() {
+            path.to_path_buf()
+        } else {
+            Path::new(SCRIPT_DIR).join(format!("{}.rhai", name_or_path))
+        };
+        Self::load(&path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = read_text(path)?;
+        Self::new(&path.display().to_string(), &source)
+    }
+}
+
+// What the script asked for, carried out once it returns
+enum Order {
+    SpawnAnts { x: f32, y: f32, count: usize },
+    DropFood { x: f32, y: f32, quantity: f32, kind: FoodKind },
+    Kill(Entity),
+    SetGoal(Entity, AiGoal),
+}
+
+// The world as the script sees it this update
+#[derive(Default)]
+struct ScriptWorld {
+    time: f64,
+    last_time: f64,
+    population: usize,
+    food: f32,
+    ants: Vec<Map>,
+    orders: Vec<Order>,
+    errors: Vec<String>,
+}
+
+struct Script {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Dynamic,
+    world: Arc<Mutex<ScriptWorld>>,
+    // Cleared when the function fails, so one error isn't repeated every update
+    tick: bool,
+    decide: bool,
+}
+
+pub struct ScriptPlugin {
+    pub script: ScriptSource,
+}
+
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut App) {
+        let world = Arc::new(Mutex::new(ScriptWorld::default()));
+        let engine = script_engine(world.clone());
+        let ast = engine
+            .compile(&self.script.source)
+            .expect("ScriptSource compiles");
+        let defines = |name: &str, params: usize| ast.iter_functions().any(|f| f.name == name && f.params.len() == params);
+        let (tick, decide) = (defines("tick", 0), defines("decide", 1));
+        if !tick && !decide {
+            println!("{} defines neither tick() nor decide(ant), so does nothing", self.script.name);
+        }
+
+        // Top level statements run once, before the first update
+        let mut scope = Scope::new();
+        if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+            println!("{}: {}", self.script.name, e);
+        }
+
+        app
+            .requires::<SimClock>("ScriptPlugin", "ClockPlugin")
+            .requires::<ColonyStats>("ScriptPlugin", "StatsPlugin")
+            .requires::<Events<FoodCreateEvent>>("ScriptPlugin", "FoodPlugin")
+            .requires::<Events<AntDeathEvent>>("ScriptPlugin", "AntPlugin")
+            .insert_resource(Script {
+                name: self.script.name.clone(),
+                engine,
+                ast,
+                scope,
+                state: Dynamic::from(Map::new()),
+                world,
+                tick,
+                decide,
+            })
+            .add_system(run_script
+                .after(BigPhase::Decide)
+                .before(BigPhase::Move)
+            );
+    }
+}
+
+fn goal_named(name: &str) -> Option<AiGoal> {
+    Some(match name {
+        "north" => AiGoal::North,
+        "south" => AiGoal::South,
+        "east" => AiGoal::East,
+        "west" => AiGoal::West,
+        "ne" => AiGoal::NE,
+        "se" => AiGoal::SE,
+        "sw" => AiGoal::SW,
+        "nw" => AiGoal::NW,
+        "random" => AiGoal::Random,
+        "wait" => AiGoal::Wait,
+        "none" => AiGoal::None,
+        _ => return None,
+    })
+}
+
+fn goal_name(goal: &AiGoal) -> &'static str {
+    match goal {
+        AiGoal::North => "north",
+        AiGoal::South => "south",
+        AiGoal::East => "east",
+        AiGoal::West => "west",
+        AiGoal::NE => "ne",
+        AiGoal::SE => "se",
+        AiGoal::SW => "sw",
+        AiGoal::NW => "nw",
+        AiGoal::Random => "random",
+        AiGoal::Destination { .. } => "destination",
+        AiGoal::Wait => "wait",
+        AiGoal::None => "none",
+    }
+}
+
+fn food_kind_named(name: &str) -> Option<FoodKind> {
+    match name {
+        "seed" => Some(FoodKind::Seed),
+        "sugar" => Some(FoodKind::Sugar),
+        "protein" => Some(FoodKind::Protein),
+        _ => None,
+    }
+}
+
+// Script ids are entity bits
+fn entity(id: i64) -> Entity {
+    Entity::from_bits(id as u64)
+}
+
+// #{x, y} heads there, a name is one of goal_named's
+fn goal_from(value: Dynamic) -> Result<Option<AiGoal>, String> {
+    if value.is_unit() {
+        return Ok(None);
+    }
+    if let Some(map) = value.read_lock::<Map>() {
+        let coord = |key: &str| map.get(key)
+            .and_then(|v| v.as_float().ok().or_else(|| v.as_int().ok().map(|i| i as f64)))
+            .ok_or_else(|| format!("A destination needs a number {}", key));
+        return Ok(Some(AiGoal::Destination {
+            dest: Position { x: coord("x")? as f32, y: coord("y")? as f32 },
+        }));
+    }
+    let name = value.into_immutable_string().map_err(|t| format!("Expected a goal, got {}", t))?;
+    goal_named(&name)
+        .map(Some)
+        .ok_or_else(|| format!("No goal called {}", name))
+}
+
+// Every function a script can call
+fn script_engine(world: Arc<Mutex<ScriptWorld>>) -> Engine {
+    let mut engine = Engine::new();
+
+    let w = world.clone();
+    engine.register_fn("time", move || w.lock().unwrap().time);
+    // True the update the clock passes seconds
+    let w = world.clone();
+    engine.register_fn("at", move |seconds: f64| {
+        let w = w.lock().unwrap();
+        w.last_time < seconds && seconds <= w.time
+    });
+    // True each update the clock passes a multiple of seconds
+    let w = world.clone();
+    engine.register_fn("every", move |seconds: f64| {
+        let w = w.lock().unwrap();
+        seconds > 0. && (w.time / seconds).floor() > (w.last_time / seconds).floor()
+    });
+    let w = world.clone();
+    engine.register_fn("population", move || w.lock().unwrap().population as i64);
+    let w = world.clone();
+    engine.register_fn("food", move || w.lock().unwrap().food as f64);
+    let w = world.clone();
+    engine.register_fn("ants", move || -> Array {
+        w.lock().unwrap().ants.iter().cloned().map(Dynamic::from).collect()
+    });
+    let w = world.clone();
+    engine.register_fn("ant", move |id: i64| -> Dynamic {
+        w.lock().unwrap().ants.iter()
+            .find(|a| a.get("id").and_then(|v| v.as_int().ok()) == Some(id))
+            .cloned()
+            .map_or(Dynamic::UNIT, Dynamic::from)
+    });
+    // From the simulation rng, so scripted runs replay
+    engine.register_fn("random", || SimRng.gen::<f64>());
+
+    let w = world.clone();
+    engine.register_fn("spawn_ants", move |x: f64, y: f64, count: i64| {
+        w.lock().unwrap().orders.push(Order::SpawnAnts { x: x as f32, y: y as f32, count: count.max(0) as usize });
+    });
+    let w = world.clone();
+    engine.register_fn("drop_food", move |x: f64, y: f64, quantity: f64| {
+        w.lock().unwrap().orders.push(Order::DropFood { x: x as f32, y: y as f32, quantity: quantity as f32, kind: FoodKind::Seed });
+    });
+    let w = world.clone();
+    engine.register_fn("drop_food", move |x: f64, y: f64, quantity: f64, kind: &str| {
+        let mut w = w.lock().unwrap();
+        match food_kind_named(kind) {
+            Some(kind) => w.orders.push(Order::DropFood { x: x as f32, y: y as f32, quantity: quantity as f32, kind }),
+            None => w.errors.push(format!("No food kind called {}", kind)),
+        }
+    });
+    let w = world.clone();
+    engine.register_fn("kill", move |id: i64| {
+        w.lock().unwrap().orders.push(Order::Kill(entity(id)));
+    });
+    // Each worker has this chance of dying
+    let w = world.clone();
+    engine.register_fn("kill_fraction", move |fraction: f64| {
+        let mut w = w.lock().unwrap();
+        let mut rng = SimRng;
+        let doomed: Vec<Entity> = w.ants.iter()
+            .filter(|a| !a.get("queen").map_or(false, |q| q.as_bool().unwrap_or(false)))
+            .filter_map(|a| a.get("id").and_then(|v| v.as_int().ok()))
+            .filter(|_| rng.gen::<f64>() < fraction)
+            .map(entity)
+            .collect();
+        w.orders.extend(doomed.into_iter().map(Order::Kill));
+    });
+    let w = world.clone();
+    engine.register_fn("set_goal", move |id: i64, goal: &str| {
+        let mut w = w.lock().unwrap();
+        match goal_named(goal) {
+            Some(goal) => w.orders.push(Order::SetGoal(entity(id), goal)),
+            None => w.errors.push(format!("No goal called {}", goal)),
+        }
+    });
+    let w = world;
+    engine.register_fn("set_destination", move |id: i64, x: f64, y: f64| {
+        w.lock().unwrap().orders.push(Order::SetGoal(entity(id), AiGoal::Destination {
+            dest: Position { x: x as f32, y: y as f32 },
+        }));
+    });
+
+    engine
+}
+
+fn ant_map(e: Entity, pos: &Position, hunger: &Hunger, health: &Health, queen: bool, ai: Option<&AntAI>) -> Map {
+    let mut ant = Map::new();
+    ant.insert("id".into(), (e.to_bits() as i64).into());
+    ant.insert("x".into(), (pos.x as f64).into());
+    ant.insert("y".into(), (pos.y as f64).into());
+    ant.insert("hunger".into(), (hunger.pct as f64).into());
+    ant.insert("health".into(), (health.pct as f64).into());
+    ant.insert("queen".into(), queen.into());
+    ant.insert("goal".into(), ai.map_or("none", |ai| goal_name(&ai.ai)).into());
+    ant
+}
+
+// Scripted goals override what BehaviourPlugin decided this update
+fn run_script(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    stats: Res<ColonyStats>,
+    mut script: ResMut<Script>,
+    mut food_writer: EventWriter<FoodCreateEvent>,
+    mut death_writer: EventWriter<AntDeathEvent>,
+    mut ants: Query<
+        (Entity, &Position, &Hunger, &Health, Option<&Queen>, Option<&mut AntAI>),
+        (With<Ant>, Without<Agent>),
+    >,
+    eating: Query<(), With<AntEating>>,
+) {
+    if clock.delta_seconds() == 0. {
+        return;
+    }
+
+    let Script { name, engine, ast, scope, state, world, tick, decide } = &mut *script;
+    {
+        let mut w = world.lock().unwrap();
+        w.last_time = w.time;
+        w.time = clock.elapsed_seconds();
+        w.population = stats.population;
+        w.food = stats.food_in_world;
+        w.ants = ants.iter_mut()
+            .map(|(e, pos, hunger, health, queen, ai)| ant_map(e, pos, hunger, health, queen.is_some(), ai.as_deref()))
+            .collect();
+    }
+
+    let failed = |function: &str, error: String| {
+        println!("{}: {}() stopped: {}", name, function, error);
+    };
+    if *tick {
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+        if let Err(e) = engine.call_fn_with_options::<Dynamic>(options, scope, ast, "tick", ()) {
+            failed("tick", e.to_string());
+            *tick = false;
+        }
+    }
+
+    // Workers busy eating finish first
+    if *decide {
+        let workers: Vec<Map> = world.lock().unwrap().ants.iter()
+            .filter(|a| !a.get("queen").map_or(false, |q| q.as_bool().unwrap_or(false)))
+            .filter(|a| a.get("id").and_then(|v| v.as_int().ok()).map_or(false, |id| eating.get(entity(id)).is_err()))
+            .cloned()
+            .collect();
+        for ant in workers.into_iter() {
+            let id = ant.get("id").and_then(|v| v.as_int().ok()).unwrap_or_default();
+            let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+            let goal = engine.call_fn_with_options::<Dynamic>(options, scope, ast, "decide", (ant,))
+                .map_err(|e| e.to_string())
+                .and_then(goal_from);
+            match goal {
+                Ok(Some(goal)) => world.lock().unwrap().orders.push(Order::SetGoal(entity(id), goal)),
+                Ok(None) => (),
+                Err(e) => {
+                    failed("decide", e);
+                    *decide = false;
+                    break;
+                },
+            }
+        }
+    }
+
+    let (orders, errors) = {
+        let mut w = world.lock().unwrap();
+        (std::mem::take(&mut w.orders), std::mem::take(&mut w.errors))
+    };
+    for error in errors.into_iter() {
+        println!("{}: {}", name, error);
+    }
+    // One death per ant, and none for those already dying of hunger
+    let mut killed: HashSet<Entity> = HashSet::new();
+    for order in orders.into_iter() {
+        match order {
+            Order::SpawnAnts { x, y, count } => {
+                for _ in 0..count {
+                    commands.spawn_bundle(AntBundle::new(x, y));
+                }
+            },
+            Order::DropFood { x, y, quantity, kind } => {
+                food_writer.send(FoodCreateEvent { x, y, quantity, kind });
+            },
+            Order::Kill(ent) => {
+                if let Ok((_, _, _, health, _, _)) = ants.get_mut(ent) {
+                    if health.pct >= 0. && killed.insert(ent) {
+                        death_writer.send(AntDeathEvent { ent });
+                    }
+                }
+            },
+            Order::SetGoal(ent, goal) => {
+                let ai = AntAI { ai: goal, duration: SCRIPT_GOAL_SECONDS };
+                match ants.get_mut(ent) {
+                    Ok((_, _, _, _, _, Some(mut current))) => *current = ai,
+                    Ok(_) => {
+                        commands.entity(ent).insert(ai);
+                    },
+                    Err(_) => (),
+                }
+            },
+        }
+    }
+}
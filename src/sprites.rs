@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use crate::arena::*;
+use crate::ant::{Ant, Queen};
+use crate::food::{Food, FOOD_FULL};
+use crate::steering::Velocity;
+use crate::clock::SimClock;
+use crate::plugins::RequireExt;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+// Art faces +x, frames are laid out left to right
+const ANT_WALK_SHEET: &str = "sprites/ant_walk.png";
+const ANT_WALK_FRAMES: usize = 4;
+const QUEEN_SPRITE: &str = "sprites/queen.png";
+// Full pile first, nearly gone last
+const FOOD_SHEET: &str = "sprites/food.png";
+const FOOD_FRAMES: usize = 4;
+// Sixteen tiles indexed by which sides have a neighbouring wall, see wall_mask
+const WALL_SHEET: &str = "sprites/walls.png";
+const WALL_FRAMES: usize = 16;
+const FRAME_SIDE: f32 = 16.;
+
+// Walk frames per arena unit moved
+const STRIDE_FRAMES: f32 = 0.08;
+
+// Sprite sheets that were found on disk.  Anything missing keeps its solid colour.
+#[derive(Default)]
+pub struct SpriteSheets {
+    pub ant: Option<Handle<TextureAtlas>>,
+    pub queen: Option<Handle<Image>>,
+    pub food: Option<Handle<TextureAtlas>>,
+    pub walls: Option<Handle<TextureAtlas>>,
+}
+
+#[derive(Component, Default)]
+struct WalkCycle {
+    // Fractional frame, advances with distance walked
+    frame: f32,
+}
+
+pub struct SpritePlugin;
+impl Plugin for SpritePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<SimClock>("SpritePlugin", "ClockPlugin")
+            .requires::<AssetServer>("SpritePlugin", "DefaultPlugins")
+            .init_resource::<SpriteSheets>()
+            .init_resource::<WallTiles>()
+            .add_startup_system(load_sprite_sheets)
+            .add_system(apply_ant_sheet)
+            .add_system(apply_queen_sprite)
+            .add_system(apply_food_sheet)
+            .add_system(autotile_walls)
+            .add_system(animate_walk)
+            .add_system(food_frames);
+    }
+}
+
+// Bevy resolves asset paths against the crate root under cargo, otherwise next to the binary
+fn asset_exists(path: &str) -> bool {
+    let root = std::env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| std::env::current_exe().ok().and_then(|exe| exe.parent().map(|p| p.to_path_buf())))
+        .unwrap_or_default();
+    root.join("assets").join(path).exists()
+}
+
+fn load_sheet(
+    asset_server: &AssetServer,
+    atlases: &mut Assets<TextureAtlas>,
+    path: &str,
+    frames: usize,
+) -> Option<Handle<TextureAtlas>> {
+    if !asset_exists(path) {
+        println!("No {}, using plain colours", path);
+        return None;
+    }
+    let texture = asset_server.load(path);
+    Some(atlases.add(TextureAtlas::from_grid(texture, Vec2::splat(FRAME_SIDE), frames, 1)))
+}
+
+fn load_sprite_sheets(
+    asset_server: Res<AssetServer>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut sheets: ResMut<SpriteSheets>,
+) {
+    sheets.ant = load_sheet(&asset_server, &mut atlases, ANT_WALK_SHEET, ANT_WALK_FRAMES);
+    sheets.food = load_sheet(&asset_server, &mut atlases, FOOD_SHEET, FOOD_FRAMES);
+    sheets.walls = load_sheet(&asset_server, &mut atlases, WALL_SHEET, WALL_FRAMES);
+    sheets.queen = if asset_exists(QUEEN_SPRITE) {
+        Some(asset_server.load(QUEEN_SPRITE))
+    } else {
+        println!("No {}, using plain colours", QUEEN_SPRITE);
+        None
+    };
+}
+
+// Swap a plain sprite for a frame of a sheet.  size_scaling sizes everything
+// as a unit square, so the frame is drawn at unit size too.
+fn use_sheet(commands: &mut Commands, e: Entity, sheet: &Handle<TextureAtlas>, color: Color, index: usize) {
+    commands.entity(e)
+        .remove::<Sprite>()
+        .remove::<Handle<Image>>()
+        .insert(sheet.clone())
+        .insert(TextureAtlasSprite {
+            color,
+            index,
+            custom_size: Some(Vec2::ONE),
+            ..Default::default()
+        });
+}
+
+fn apply_ant_sheet(
+    mut commands: Commands,
+    sheets: Res<SpriteSheets>,
+    ants: Query<(Entity, &Sprite), (Added<Ant>, Without<Queen>)>,
+) {
+    if let Some(sheet) = &sheets.ant {
+        for (e, sprite) in ants.iter() {
+            use_sheet(&mut commands, e, sheet, sprite.color, 0);
+            commands.entity(e).insert(WalkCycle::default());
+        }
+    }
+}
+
+fn apply_queen_sprite(
+    sheets: Res<SpriteSheets>,
+    mut queens: Query<(&mut Sprite, &mut Handle<Image>), Added<Queen>>,
+) {
+    if let Some(image) = &sheets.queen {
+        for (mut sprite, mut texture) in queens.iter_mut() {
+            sprite.custom_size = Some(Vec2::ONE);
+            *texture = image.clone();
+        }
+    }
+}
+
+fn apply_food_sheet(
+    mut commands: Commands,
+    sheets: Res<SpriteSheets>,
+    food: Query<(Entity, &Sprite, &Food), Added<Food>>,
+) {
+    if let Some(sheet) = &sheets.food {
+        for (e, sprite, f) in food.iter() {
+            use_sheet(&mut commands, e, sheet, sprite.color, food_frame(f.quantity));
+        }
+    }
+}
+
+fn food_frame(quantity: f32) -> usize {
+    let eaten = 1. - (quantity / FOOD_FULL).clamp(0., 1.);
+    ((eaten * FOOD_FRAMES as f32) as usize).min(FOOD_FRAMES - 1)
+}
+
+fn food_frames(
+    mut food: Query<(&Food, &mut TextureAtlasSprite), Changed<Food>>,
+) {
+    for (f, mut sprite) in food.iter_mut() {
+        let frame = food_frame(f.quantity);
+        if sprite.index != frame {
+            sprite.index = frame;
+        }
+    }
+}
+
+fn animate_walk(
+    clock: Res<SimClock>,
+    mut ants: Query<(&Velocity, &mut WalkCycle, &mut TextureAtlasSprite)>,
+) {
+    let dt = clock.delta_seconds();
+    for (velocity, mut walk, mut sprite) in ants.iter_mut() {
+        // Legs keep pace with the ground covered, standing ants hold their frame
+        walk.frame = (walk.frame + velocity.0.length() * dt * STRIDE_FRAMES) % ANT_WALK_FRAMES as f32;
+        let frame = walk.frame as usize;
+        if sprite.index != frame {
+            sprite.index = frame;
+        }
+    }
+}
+
+// Bit per side with a wall next to it: north 1, east 2, south 4, west 8
+fn wall_mask(is_wall: impl Fn(u32, u32) -> bool, col: u32, row: u32) -> usize {
+    let mut mask = 0;
+    if is_wall(col, row + 1) {
+        mask |= 1;
+    }
+    if is_wall(col + 1, row) {
+        mask |= 2;
+    }
+    if row > 0 && is_wall(col, row - 1) {
+        mask |= 4;
+    }
+    if col > 0 && is_wall(col - 1, row) {
+        mask |= 8;
+    }
+    mask
+}
+
+// The tile and its four neighbours, whose masks change when it does
+fn around(col: u32, row: u32) -> impl Iterator<Item = (u32, u32)> {
+    let mut tiles = vec![(col, row), (col, row + 1), (col + 1, row)];
+    if row > 0 {
+        tiles.push((col, row - 1));
+    }
+    if col > 0 {
+        tiles.push((col - 1, row));
+    }
+    tiles.into_iter()
+}
+
+// Where every wall stands, so one that's gone can still be found
+#[derive(Default)]
+struct WallTiles {
+    by_entity: HashMap<Entity, (u32, u32)>,
+    by_tile: HashMap<(u32, u32), Entity>,
+}
+
+// A wall added or knocked down changes its neighbours' tiles, so redo just those
+fn autotile_walls(
+    mut commands: Commands,
+    sheets: Res<SpriteSheets>,
+    mut walls: ResMut<WallTiles>,
+    added: Query<(Entity, &Position, &CollisionGroups), Added<CollisionGroups>>,
+    // Despawns count too, which is how walls are knocked down
+    removed: RemovedComponents<CollisionGroups>,
+    plain: Query<(), With<Sprite>>,
+    mut tiled: Query<&mut TextureAtlasSprite>,
+) {
+    let sheet = match &sheets.walls {
+        Some(s) => s,
+        None => return,
+    };
+
+    let mut changed: HashSet<(u32, u32)> = HashSet::new();
+    for e in removed.iter() {
+        // Anything else with collision groups, ants and food, was never tracked
+        if let Some(tile) = walls.by_entity.remove(&e) {
+            if walls.by_tile.get(&tile) == Some(&e) {
+                walls.by_tile.remove(&tile);
+            }
+            changed.extend(around(tile.0, tile.1));
+        }
+    }
+    for (e, p, g) in added.iter() {
+        if g.memberships & CollisionGroups::WALL == 0 {
+            continue;
+        }
+        if let Some(tile) = tile_of(p) {
+            walls.by_entity.insert(e, tile);
+            walls.by_tile.insert(tile, e);
+            changed.extend(around(tile.0, tile.1));
+        }
+    }
+
+    for (col, row) in changed.into_iter() {
+        let e = match walls.by_tile.get(&(col, row)) {
+            Some(e) => *e,
+            None => continue,
+        };
+        let index = wall_mask(|c, r| walls.by_tile.contains_key(&(c, r)), col, row);
+        if let Ok(mut sprite) = tiled.get_mut(e) {
+            sprite.index = index;
+        } else if plain.get(e).is_ok() {
+            use_sheet(&mut commands, e, sheet, Color::WHITE, index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_mask_sides() {
+        let walls: HashSet<(u32, u32)> = [(5, 5), (5, 6), (6, 5), (5, 4), (4, 5)].into_iter().collect();
+        let is_wall = |c: u32, r: u32| walls.contains(&(c, r));
+        assert_eq!(wall_mask(is_wall, 5, 5), 15);
+        assert_eq!(wall_mask(is_wall, 5, 6), 4);
+        assert_eq!(wall_mask(is_wall, 6, 5), 8);
+        assert_eq!(wall_mask(is_wall, 5, 4), 1);
+        assert_eq!(wall_mask(is_wall, 4, 5), 2);
+    }
+
+    #[test]
+    fn wall_mask_at_the_edge() {
+        let walls: HashSet<(u32, u32)> = [(0, 0), (1, 0)].into_iter().collect();
+        let is_wall = |c: u32, r: u32| walls.contains(&(c, r));
+        assert_eq!(wall_mask(is_wall, 0, 0), 2);
+        assert_eq!(wall_mask(is_wall, 1, 0), 8);
+    }
+
+    #[test]
+    fn around_stays_on_the_arena() {
+        let tiles: HashSet<(u32, u32)> = around(0, 0).collect();
+        let expected: HashSet<(u32, u32)> = [(0, 0), (0, 1), (1, 0)].into_iter().collect();
+        assert_eq!(tiles, expected);
+        assert_eq!(around(3, 3).count(), 5);
+    }
+
+    #[test]
+    fn food_frames_run_full_to_gone() {
+        assert_eq!(food_frame(FOOD_FULL), 0);
+        assert_eq!(food_frame(FOOD_FULL * 2.), 0);
+        assert_eq!(food_frame(0.), FOOD_FRAMES - 1);
+        assert!(food_frame(FOOD_FULL / 2.) > 0);
+    }
+}
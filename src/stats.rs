@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use crate::clock::SimClock;
+use crate::ant::*;
+use crate::food::Food;
+use crate::fog::FogMap;
+use crate::plugins::RequireExt;
+use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+use std::path::Path;
+
+// Seconds of simulation between history samples
+const SAMPLE_INTERVAL: f64 = 1.;
+pub const HISTORY_MINUTES: usize = 5;
+const HISTORY_SAMPLES: usize = HISTORY_MINUTES * 60;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatsSample {
+    pub time: f64,
+    pub population: usize,
+    pub food: f32,
+}
+
+#[derive(Default)]
+pub struct ColonyStats {
+    pub population: usize,
+    pub peak_population: usize,
+    pub deaths: u32,
+    pub births: u32,
+    pub food_in_world: f32,
+    pub average_hunger: f32,
+    pub fog_uncovered: f32,
+    pub food_eaten: f32,
+    // Summed lifetimes of every ant so far
+    pub ant_seconds: f64,
+    pub history: VecDeque<StatsSample>,
+    started: bool,
+    next_sample: f64,
+}
+
+impl ColonyStats {
+    // One row per history sample
+    pub fn write_csv(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        writeln!(file, "time,population,food")?;
+        for sample in self.history.iter() {
+            writeln!(file, "{:.1},{},{:.2}", sample.time, sample.population, sample.food)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct StatsPlugin;
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ColonyStats>()
+            .requires::<SimClock>("StatsPlugin", "ClockPlugin")
+            .requires::<Events<AntDeathEvent>>("StatsPlugin", "AntPlugin")
+            .requires::<Events<FoodEatenEvent>>("StatsPlugin", "AntPlugin")
+            .add_system(count_births_and_deaths
+                .after(BigPhase::Ambient)
+            )
+            .add_system(update_stats
+                .after(BigPhase::Cleanup)
+            );
+    }
+}
+
+fn count_births_and_deaths(
+    mut stats: ResMut<ColonyStats>,
+    mut deaths: EventReader<AntDeathEvent>,
+    mut eaten: EventReader<FoodEatenEvent>,
+    born: Query<Entity, Added<Ant>>,
+) {
+    // An ant can be reported dead twice in one update
+    let dead: HashSet<Entity> = deaths.iter().map(|d| d.ent).collect();
+    stats.deaths += dead.len() as u32;
+    stats.food_eaten += eaten.iter().map(|e| e.amount).sum::<f32>();
+
+    // The starting colony isn't born
+    let new_ants = born.iter().count() as u32;
+    if stats.started {
+        stats.births += new_ants;
+    }
+    stats.started = true;
+}
+
+fn update_stats(
+    clock: Res<SimClock>,
+    fog: Option<Res<FogMap>>,
+    mut stats: ResMut<ColonyStats>,
+    ants: Query<&Hunger, With<Ant>>,
+    food: Query<&Food>,
+) {
+    stats.population = ants.iter().count();
+    stats.ant_seconds += stats.population as f64 * clock.delta_seconds() as f64;
+    stats.peak_population = stats.peak_population.max(stats.population);
+    stats.average_hunger = if stats.population > 0 {
+        ants.iter().map(|h| h.pct).sum::<f32>() / stats.population as f32
+    } else {
+        0.
+    };
+    stats.food_in_world = food.iter().map(|f| f.quantity.max(0.)).sum();
+    // Without fog everything's in view
+    stats.fog_uncovered = fog.map(|f| f.uncovered_fraction()).unwrap_or(1.);
+
+    let now = clock.elapsed_seconds();
+    if now >= stats.next_sample {
+        stats.next_sample = now + SAMPLE_INTERVAL;
+        let sample = StatsSample {
+            time: now,
+            population: stats.population,
+            food: stats.food_in_world,
+        };
+        stats.history.push_back(sample);
+        while stats.history.len() > HISTORY_SAMPLES {
+            stats.history.pop_front();
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+use crate::arena::*;
+use crate::arena::Size;
+use rand::Rng;
+use crate::rng::SimRng;
+use std::f32::consts::PI;
+
+// Radians either side of the current heading a wandering ant may drift per tick
+const WANDER_JITTER: f32 = 0.6;
+// How strongly a directional goal pulls the wander
+const WANDER_BIAS: f32 = 0.5;
+// Obstacles closer than this (past both edges) push the ant away
+pub const AVOID_DISTANCE: f32 = 2. * ARENA_TILE_SIDE;
+// Nestmates closer than this (past both edges) push the ant away
+const SEPARATION_DISTANCE: f32 = ARENA_TILE_SIDE;
+
+// Direction the ant faces, radians counter-clockwise from east
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Heading {
+    pub angle: f32,
+}
+
+impl Heading {
+    pub fn random() -> Self {
+        Heading {
+            angle: SimRng.gen_range(-PI, PI),
+        }
+    }
+
+    pub fn direction(&self) -> Vec2 {
+        Vec2::new(self.angle.cos(), self.angle.sin())
+    }
+
+    // Rotate towards desired, never more than max_turn radians
+    pub fn turn_towards(&mut self, desired: Vec2, max_turn: f32) {
+        let target = desired.y.atan2(desired.x);
+        let diff = wrap_angle(target - self.angle);
+        self.angle = wrap_angle(self.angle + diff.clamp(-max_turn, max_turn));
+    }
+}
+
+// Arena units per second, as of the last move
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Velocity(pub Vec2);
+
+#[derive(Clone, Copy, Debug)]
+pub struct SteeringWeights {
+    pub wander: f32,
+    pub seek: f32,
+    pub avoid_walls: f32,
+    pub separate: f32,
+}
+
+impl Default for SteeringWeights {
+    fn default() -> Self {
+        SteeringWeights {
+            wander: 1.0,
+            seek: 2.0,
+            avoid_walls: 3.0,
+            separate: 1.5,
+        }
+    }
+}
+
+fn wrap_angle(mut angle: f32) -> f32 {
+    while angle > PI {
+        angle -= 2. * PI;
+    }
+    while angle < -PI {
+        angle += 2. * PI;
+    }
+    angle
+}
+
+pub fn wander<R: Rng>(angle: f32, bias: Option<Vec2>, rng: &mut R) -> Vec2 {
+    let drifted = angle + rng.gen_range(-WANDER_JITTER, WANDER_JITTER);
+    let drift = Vec2::new(drifted.cos(), drifted.sin());
+    match bias {
+        Some(b) => (drift + b * WANDER_BIAS).normalize_or_zero(),
+        None => drift,
+    }
+}
+
+pub fn seek(from: Vec2, target: Vec2) -> Vec2 {
+    (target - from).normalize_or_zero()
+}
+
+// Push away from anything within AVOID_DISTANCE, harder the closer it is
+pub fn avoid_obstacles(from: Vec2, size: &Size, obstacles: &[(Position, Size)]) -> Vec2 {
+    let p = Position { x: from.x, y: from.y };
+    obstacles.iter()
+        .filter_map(|(o_p, o_s)| {
+            let gap = dist_between(&p, size, o_p, o_s);
+            if gap >= AVOID_DISTANCE {
+                return None;
+            }
+            let away = (from - Vec2::from((o_p.x, o_p.y))).normalize_or_zero();
+            Some(away * (1. - gap.max(0.) / AVOID_DISTANCE))
+        })
+        .fold(Vec2::ZERO, |acc, v| acc + v)
+}
+
+pub fn separate(from: Vec2, size: &Size, others: &[(Position, Size)]) -> Vec2 {
+    let p = Position { x: from.x, y: from.y };
+    others.iter()
+        .filter_map(|(o_p, o_s)| {
+            let centre = Vec2::from((o_p.x, o_p.y));
+            // Skip ourselves
+            if from.distance(centre) < 0.001 {
+                return None;
+            }
+            let gap = dist_between(&p, size, o_p, o_s);
+            if gap >= SEPARATION_DISTANCE {
+                return None;
+            }
+            let away = (from - centre).normalize_or_zero();
+            Some(away * (1. - gap.max(0.) / SEPARATION_DISTANCE))
+        })
+        .fold(Vec2::ZERO, |acc, v| acc + v)
+}
+
+pub fn heading_rotation(
+    mut q: Query<
+        (&Heading, &mut Transform),
+        Changed<Heading>,
+    >
+) {
+    for (heading, mut transform) in q.iter_mut() {
+        transform.rotation = Quat::from_rotation_z(heading.angle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_angle_stays_within_pi() {
+        assert!((wrap_angle(3. * PI) - PI).abs() < 1e-5);
+        assert!((wrap_angle(-2.5 * PI) + 0.5 * PI).abs() < 1e-5);
+        assert_eq!(wrap_angle(1.), 1.);
+    }
+
+    #[test]
+    fn turn_is_limited() {
+        let mut heading = Heading { angle: 0. };
+        heading.turn_towards(Vec2::new(0., 1.), 0.1);
+        assert!((heading.angle - 0.1).abs() < 1e-5);
+        heading.turn_towards(Vec2::new(0., -1.), 10.);
+        assert!((heading.angle + PI / 2.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn turns_the_short_way_round() {
+        let mut heading = Heading { angle: 0.9 * PI };
+        heading.turn_towards(Vec2::new(-1., -0.1), 0.5);
+        // Across the ±π seam, not back through 0
+        assert!(heading.angle.abs() > 0.9 * PI, "{}", heading.angle);
+    }
+
+    #[test]
+    fn seek_points_at_the_target() {
+        assert_eq!(seek(Vec2::ZERO, Vec2::new(10., 0.)), Vec2::new(1., 0.));
+        assert_eq!(seek(Vec2::ONE, Vec2::ONE), Vec2::ZERO);
+    }
+
+    #[test]
+    fn avoid_pushes_away_and_ignores_far_walls() {
+        let size = Size::square(1.);
+        let near = [(Position { x: 20., y: 0. }, Size::square(1.))];
+        let push = avoid_obstacles(Vec2::ZERO, &size, &near);
+        assert!(push.x < 0. && push.y.abs() < 1e-5, "{:?}", push);
+        let far = [(Position { x: 500., y: 0. }, Size::square(1.))];
+        assert_eq!(avoid_obstacles(Vec2::ZERO, &size, &far), Vec2::ZERO);
+    }
+
+    #[test]
+    fn separate_skips_itself() {
+        let size = Size::square(1.);
+        let others = [(Position { x: 0., y: 0. }, Size::square(1.))];
+        assert_eq!(separate(Vec2::ZERO, &size, &others), Vec2::ZERO);
+    }
+}
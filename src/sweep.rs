@@ -0,0 +1,243 @@
+use crate::ant::AntParams;
+use crate::config::{load_ron, SimConfig};
+use crate::headless::{self, Outcome, RunReport};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// A grid of AntParams, each run once per seed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SweepSpec {
+    // Shared by every run, its params fill in anything not swept
+    #[serde(default)]
+    pub base: SimConfig,
+    pub seeds: Vec<u64>,
+    // Values to try for each AntParams field, empty keeps the base value
+    #[serde(default)]
+    pub hunger_rate: Vec<f32>,
+    #[serde(default)]
+    pub eat_rate: Vec<f32>,
+    #[serde(default)]
+    pub food_goal_hunger: Vec<f32>,
+}
+
+impl SweepSpec {
+    pub fn from_ron(source: &str) -> Result<Self, String> {
+        ron::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_ron(path)
+    }
+
+    // Every combination of the swept values
+    pub fn grid(&self) -> Vec<AntParams> {
+        let base = self.base.params;
+        let or_base = |values: &[f32], b: f32| if values.is_empty() { vec![b] } else { values.to_vec() };
+        let mut grid = Vec::new();
+        for hunger_rate in or_base(&self.hunger_rate, base.hunger_rate) {
+            for eat_rate in or_base(&self.eat_rate, base.eat_rate) {
+                for food_goal_hunger in or_base(&self.food_goal_hunger, base.food_goal_hunger) {
+                    grid.push(AntParams {
+                        hunger_rate,
+                        eat_rate,
+                        food_goal_hunger,
+                    });
+                }
+            }
+        }
+        grid
+    }
+}
+
+// One configuration, averaged over its seeds
+#[derive(Clone, Debug)]
+pub struct SweepRow {
+    pub params: AntParams,
+    pub runs: usize,
+    pub survived: usize,
+    pub survival_seconds: f64,
+    pub peak_population: f64,
+    pub food_efficiency: f64,
+}
+
+impl SweepRow {
+    fn new(params: AntParams, reports: &[RunReport]) -> Self {
+        let n = reports.len().max(1) as f64;
+        let mean = |f: fn(&RunReport) -> f64| reports.iter().map(f).sum::<f64>() / n;
+        SweepRow {
+            params,
+            runs: reports.len(),
+            survived: reports.iter().filter(|r| r.outcome == Outcome::Survived).count(),
+            survival_seconds: mean(|r| r.sim_seconds),
+            peak_population: mean(|r| r.peak_population as f64),
+            food_efficiency: mean(|r| r.food_efficiency()),
+        }
+    }
+}
+
+// Runs every configuration and seed, jobs at a time.  Output for each run goes
+// under out/config_N/seed_S.
+pub fn run_sweep(spec: &SweepSpec, exe: &Path, out: &Path, jobs: usize) -> Result<Vec<SweepRow>, String> {
+    let grid = spec.grid();
+    let mut runs = Vec::new();
+    for (i, params) in grid.iter().enumerate() {
+        for seed in spec.seeds.iter() {
+            let config = SimConfig {
+                seed: Some(*seed),
+                params: *params,
+                ..spec.base.clone()
+            };
+            runs.push((config, out.join(format!("config_{}", i)).join(format!("seed_{}", seed))));
+        }
+    }
+    println!("Sweeping {} configurations x {} seeds on {} jobs", grid.len(), spec.seeds.len(), jobs);
+    let results = run_parallel(exe, runs, jobs);
+
+    let total = results.len();
+    let mut failed = 0;
+    let rows = grid.iter()
+        .zip(results.chunks(spec.seeds.len().max(1)))
+        .map(|(params, results)| {
+            let reports: Vec<RunReport> = results.iter()
+                .filter_map(|r| r.as_ref().ok().cloned())
+                .collect();
+            failed += results.len() - reports.len();
+            SweepRow::new(*params, &reports)
+        })
+        .collect();
+    if failed > 0 {
+        println!("{} of {} runs failed and are left out of the summary", failed, total);
+    }
+    Ok(rows)
+}
+
+// Each run is `exe headless` writing to its directory, jobs at a time.  They
+// get a process each because the simulation rng is global to one.
+// Results come back in the order of runs.
+pub fn run_parallel(exe: &Path, runs: Vec<(SimConfig, PathBuf)>, jobs: usize) -> Vec<Result<RunReport, String>> {
+    let total = runs.len();
+    let queue = Arc::new(Mutex::new(runs.into_iter().enumerate().collect::<Vec<_>>()));
+    let results: Arc<Mutex<Vec<Option<Result<RunReport, String>>>>> =
+        Arc::new(Mutex::new((0..total).map(|_| None).collect()));
+    let done = Arc::new(Mutex::new(0));
+    let workers: Vec<_> = (0..jobs.max(1))
+        .map(|_| {
+            let queue = queue.clone();
+            let results = results.clone();
+            let done = done.clone();
+            let exe = exe.to_path_buf();
+            thread::spawn(move || loop {
+                let (i, (config, dir)) = match queue.lock().unwrap().pop() {
+                    Some(run) => run,
+                    None => break,
+                };
+                let result = run_one(&exe, &config, &dir);
+                let mut done = done.lock().unwrap();
+                *done += 1;
+                match &result {
+                    Ok(report) => println!(
+                        "[{}/{}] {}: {:?} after {:.0}s",
+                        *done, total, dir.display(), report.outcome, report.sim_seconds,
+                    ),
+                    Err(e) => println!("[{}/{}] {}", *done, total, e),
+                }
+                results.lock().unwrap()[i] = Some(result);
+            })
+        })
+        .collect();
+    for worker in workers.into_iter() {
+        if worker.join().is_err() {
+            println!("A run worker panicked");
+        }
+    }
+
+    let results = std::mem::take(&mut *results.lock().unwrap());
+    results.into_iter()
+        .map(|r| r.unwrap_or_else(|| Err("Run lost to a panicked worker".to_string())))
+        .collect()
+}
+
+fn run_one(exe: &Path, config: &SimConfig, dir: &Path) -> Result<RunReport, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let config_path = dir.join("config.ron");
+    config.save(&config_path)?;
+
+    let status = Command::new(exe)
+        .arg("headless")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--out")
+        .arg(dir)
+        .stdout(Stdio::null())
+        .status()
+        .map_err(|e| format!("{}: {}", exe.display(), e))?;
+    // Survived, died and failed objectives are all finished runs
+    if !matches!(status.code(), Some(0) | Some(1) | Some(3)) {
+        return Err(format!("Run in {} failed: {}", dir.display(), status));
+    }
+    headless::load_report(&dir.join("report.ron"))
+}
+
+pub fn write_summary(rows: &[SweepRow], path: &Path) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    writeln!(file, "hunger_rate,eat_rate,food_goal_hunger,runs,survived,survival_seconds,peak_population,food_efficiency")?;
+    for row in rows.iter() {
+        writeln!(
+            file,
+            "{},{},{},{},{},{:.1},{:.1},{:.1}",
+            row.params.hunger_rate, row.params.eat_rate, row.params.food_goal_hunger,
+            row.runs, row.survived, row.survival_seconds, row.peak_population, row.food_efficiency,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(hunger_rate: Vec<f32>, eat_rate: Vec<f32>, food_goal_hunger: Vec<f32>) -> SweepSpec {
+        SweepSpec {
+            base: SimConfig::default(),
+            seeds: vec![1],
+            hunger_rate,
+            eat_rate,
+            food_goal_hunger,
+        }
+    }
+
+    #[test]
+    fn nothing_swept_is_the_base() {
+        let grid = spec(vec![], vec![], vec![]).grid();
+        let base = AntParams::default();
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid[0].hunger_rate, base.hunger_rate);
+        assert_eq!(grid[0].eat_rate, base.eat_rate);
+        assert_eq!(grid[0].food_goal_hunger, base.food_goal_hunger);
+    }
+
+    #[test]
+    fn every_combination() {
+        let grid = spec(vec![0.01, 0.02], vec![0.5, 1., 2.], vec![0.3]).grid();
+        assert_eq!(grid.len(), 6);
+        for hunger_rate in [0.01, 0.02] {
+            for eat_rate in [0.5, 1., 2.] {
+                assert!(grid.iter().any(|p| p.hunger_rate == hunger_rate && p.eat_rate == eat_rate && p.food_goal_hunger == 0.3));
+            }
+        }
+    }
+
+    #[test]
+    fn unswept_fields_keep_the_base() {
+        let mut spec = spec(vec![], vec![2.], vec![]);
+        spec.base.params.hunger_rate = 0.04;
+        let grid = spec.grid();
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid[0].hunger_rate, 0.04);
+        assert_eq!(grid[0].eat_rate, 2.);
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use crate::clock::SimClock;
+use crate::arena::*;
+use crate::ant::*;
+use crate::genome::Genome;
+use crate::gym::Agent;
+use crate::plugins::RequireExt;
+
+pub struct TrophallaxisParams {
+    // Hunger pct per second passed from donor to recipient
+    pub rate: f32,
+    // Arena units between the two ants
+    pub range: f32,
+    // Donors never give away food below this
+    pub donor_min: f32,
+    // Recipients stop taking food above this
+    pub fill_to: f32,
+}
+
+impl Default for TrophallaxisParams {
+    fn default() -> Self {
+        TrophallaxisParams {
+            rate: 0.2,
+            range: 1.5 * ARENA_TILE_SIDE,
+            donor_min: 0.6,
+            fill_to: 0.5,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FoodSharedEvent {
+    pub donor: Entity,
+    pub recipient: Entity,
+    pub amount: f32,
+}
+
+pub struct TrophallaxisPlugin;
+impl Plugin for TrophallaxisPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .requires::<SimClock>("TrophallaxisPlugin", "ClockPlugin")
+            .requires::<AntParams>("TrophallaxisPlugin", "AntPlugin")
+            .init_resource::<TrophallaxisParams>()
+            .add_event::<FoodSharedEvent>()
+            .add_system(share_food
+                .label(BigPhase::Act)
+                .after(BigPhase::Move)
+            );
+    }
+}
+
+fn share_food(
+    clock: Res<SimClock>,
+    params: Res<TrophallaxisParams>,
+    ant_params: Res<AntParams>,
+    mut shared_writer: EventWriter<FoodSharedEvent>,
+    // The trainer's agent only eats what it finds itself
+    mut ants: Query<(Entity, &Position, &mut Hunger, Option<&Queen>, Option<&AntEating>, Option<&Genome>), (With<Ant>, Without<Agent>)>,
+) {
+    let max_transfer = clock.delta_seconds() * params.rate;
+    if max_transfer <= 0. {
+        return;
+    }
+
+    let mut donors: Vec<(Entity, Vec2, f32)> = Vec::new();
+    let mut recipients: Vec<(Entity, Vec2, f32)> = Vec::new();
+    for (e, p, hunger, opt_queen, opt_eating, opt_genome) in ants.iter_mut() {
+        let at = Vec2::from((p.x, p.y));
+        // Same threshold the ant forages at
+        let hungry_below = match (opt_genome, opt_queen) {
+            (Some(genome), None) => genome.food_goal_hunger,
+            _ => ant_params.food_goal_hunger,
+        };
+        if opt_queen.is_none() && hunger.pct > params.donor_min {
+            donors.push((e, at, hunger.pct));
+        } else if opt_eating.is_none() && hunger.pct < hungry_below {
+            recipients.push((e, at, hunger.pct));
+        }
+    }
+
+    // Hungriest first, each donor feeds one nestmate per tick
+    recipients.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+    let mut transfers: Vec<FoodSharedEvent> = Vec::new();
+    for (recipient, r_at, r_pct) in recipients.iter() {
+        let closest = donors.iter()
+            .enumerate()
+            .map(|(i, (_, d_at, _))| (i, r_at.distance(*d_at)))
+            .filter(|(_, d)| *d < params.range)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        if let Some((i, _)) = closest {
+            let (donor, _, d_pct) = donors.swap_remove(i);
+            let amount = max_transfer
+                .min(d_pct - params.donor_min)
+                .min(params.fill_to - r_pct);
+            if amount > 0. {
+                transfers.push(FoodSharedEvent {
+                    donor,
+                    recipient: *recipient,
+                    amount,
+                });
+            }
+        }
+    }
+
+    for transfer in transfers.into_iter() {
+        if let Ok((_, _, mut hunger, _, _, _)) = ants.get_mut(transfer.donor) {
+            hunger.pct -= transfer.amount;
+        }
+        if let Ok((_, _, mut hunger, _, _, _)) = ants.get_mut(transfer.recipient) {
+            hunger.pct += transfer.amount;
+        }
+        shared_writer.send(transfer);
+    }
+}
@@ -1,29 +1,88 @@
 use bevy::prelude::*;
 use crate::arena::*;
+use crate::map::MapDef;
+use std::path::PathBuf;
+
+// Where the walls come from.  Tiles are (col, row).
+#[derive(Clone)]
+pub enum WallSource {
+    // Just the arena border
+    Border,
+    // The border plus the running map's walls
+    Map,
+    // The border plus the walls of another map file
+    MapFile(PathBuf),
+    // Every wall tile, border included, comes from the function
+    Generator(fn() -> Vec<(u32, u32)>),
+}
+
+impl Default for WallSource {
+    fn default() -> Self {
+        WallSource::Map
+    }
+}
 
 pub struct WallPlugin;
 
 impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<WallSource>()
             .add_startup_system(startup_spawn_tiles.after(StartupLabels::Screen));
     }
 }
 
-fn startup_spawn_tiles(mut commands: Commands) {
+pub fn border_tiles() -> Vec<(u32, u32)> {
+    let mut tiles = Vec::new();
     for row in [0, ARENA_HEIGHT_TILES-1].into_iter() {
         for col in 0..ARENA_WIDTH_TILES {
-            spawn_tile(&mut commands, ARENA_TILE_SIDE * col as f32, ARENA_TILE_SIDE * row as f32);
+            tiles.push((col, row));
         }
     }
     for col in [0, ARENA_WIDTH_TILES-1].into_iter() {
-        for row in 0..ARENA_HEIGHT_TILES {
+        for row in 1..ARENA_HEIGHT_TILES-1 {
+            tiles.push((col, row));
+        }
+    }
+    tiles
+}
+
+fn wall_tiles(source: &WallSource, map: Option<&MapDef>) -> Vec<(u32, u32)> {
+    let mut tiles = match source {
+        WallSource::Generator(generate) => return generate(),
+        _ => border_tiles(),
+    };
+    match source {
+        WallSource::Map => {
+            if let Some(map) = map {
+                tiles.extend(map.walls.iter().copied());
+            }
+        },
+        WallSource::MapFile(path) => match MapDef::load(path) {
+            Ok(map) => tiles.extend(map.walls.iter().copied()),
+            Err(e) => println!("Couldn't load walls, using the border: {}", e),
+        },
+        _ => {},
+    }
+    tiles
+}
+
+fn startup_spawn_tiles(
+    mut commands: Commands,
+    source: Res<WallSource>,
+    map: Option<Res<MapDef>>,
+) {
+    let mut tiles = wall_tiles(&source, map.as_deref());
+    tiles.sort_unstable();
+    tiles.dedup();
+    for (col, row) in tiles.into_iter() {
+        if col < ARENA_WIDTH_TILES && row < ARENA_HEIGHT_TILES {
             spawn_tile(&mut commands, ARENA_TILE_SIDE * col as f32, ARENA_TILE_SIDE * row as f32);
         }
     }
 }
 
-fn spawn_tile(commands: &mut Commands, x: f32, y: f32) {
+pub fn spawn_tile(commands: &mut Commands, x: f32, y: f32) {
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
@@ -35,5 +94,5 @@ fn spawn_tile(commands: &mut Commands, x: f32, y: f32) {
         .insert(Position{ x, y })
         .insert(crate::arena::Size::square(0.95))
         .insert(Layer::Main1)
-        .insert(Collides);
-}
\ No newline at end of file
+        .insert(CollisionGroups::wall());
+}